@@ -1,11 +1,17 @@
 #[cfg(feature = "server")]
-use std::fs;
+use std::{
+    fs,
+    sync::{Arc, OnceLock},
+};
 
 use dioxus::{logger::tracing::info, prelude::*};
 #[cfg(feature = "server")]
-use pawn_runtime::{Component, Runtime};
+use pawn_runtime::{
+    CapabilityPolicy, ComponentBuilder, ComponentRegistry, Credential, HttpPolicy, Integrity,
+    Runtime,
+};
 #[cfg(feature = "server")]
-use wasmtime::component::Val;
+use wasmtime::component::{ComponentType, Lift, Lower};
 
 #[cfg(feature = "server")]
 use crate::backend::config::get_config;
@@ -13,57 +19,79 @@ use crate::backend::config::get_config;
 use crate::backend::config::Config;
 use crate::components::Message;
 
+/// The `pawn:chat/types.message` record exchanged with the chat component.
+#[cfg(feature = "server")]
+#[derive(ComponentType, Lift, Lower)]
+#[component(record)]
+struct ChatMessage {
+    role: String,
+    content: String,
+}
+
+/// The registry shared by all chat requests, so each component is compiled only once.
+#[cfg(feature = "server")]
+static REGISTRY: OnceLock<ComponentRegistry> = OnceLock::new();
+
+#[cfg(feature = "server")]
+fn registry() -> Result<&'static ComponentRegistry, ServerFnError> {
+    if let Some(registry) = REGISTRY.get() {
+        return Ok(registry);
+    }
+    let runtime: Runtime = Runtime::new()?;
+    Ok(REGISTRY.get_or_init(|| ComponentRegistry::new(Arc::new(runtime))))
+}
+
 #[server]
 pub async fn chat(messages: Vec<Message>) -> Result<Message, ServerFnError> {
     let config: Config = get_config()?;
     let chat_config = config.chat;
-    let wasm: Vec<u8> = fs::read(chat_config.component_wasm_path)?;
-    let runtime: Runtime = Runtime::new()?;
-    let component = Component::with_runtime(wasm.as_slice(), &runtime)?;
-    let messages: Vec<Val> = messages
+    let registry = registry()?;
+    let component = match registry.get(&chat_config.component_wasm_path) {
+        Ok(component) => component,
+        Err(_) => {
+            let wasm: Vec<u8> = fs::read(&chat_config.component_wasm_path)?;
+            // The component may only call the API of the configured provider
+            let authority =
+                cloud_ai::get_chat_authority(&chat_config.provider).ok_or_else(|| {
+                    ServerFnError::new(format!("Unsupported provider: {}", chat_config.provider))
+                })?;
+            // The runtime attaches the API key to the component's requests, so it never enters
+            // the guest
+            registry
+                .runtime()
+                .secrets
+                .insert(authority.clone(), Credential::Bearer(chat_config.api_key.clone()));
+            let capabilities = CapabilityPolicy {
+                http: HttpPolicy::allow_authorities([authority]),
+                ..Default::default()
+            };
+            let integrity = chat_config
+                .component_sha256
+                .clone()
+                .map_or(Integrity::Unverified, Integrity::Sha256);
+            registry.register(
+                &chat_config.component_wasm_path,
+                ComponentBuilder::default()
+                    .wasm(wasm.as_slice())
+                    .capabilities(capabilities)
+                    .integrity(integrity),
+            )?
+        }
+    };
+    let messages: Vec<ChatMessage> = messages
         .iter()
-        .map(|m| {
-            Val::Record(vec![
-                ("role".to_string(), Val::String(m.role.to_string())),
-                ("content".to_string(), Val::String(m.content.to_string())),
-            ])
-        })
+        .map(|m| ChatMessage { role: m.role.to_string(), content: m.content.to_string() })
         .collect();
 
-    let results = component
-        .call(Some(&chat_config.component_handler), &chat_config.component_handle_function, &vec![
-            Val::String(chat_config.provider),
-            Val::String(chat_config.model),
-            Val::String(chat_config.api_key),
-            Val::List(messages),
-        ])
+    let ((message,), report) = registry
+        .call_typed::<(String, String, Vec<ChatMessage>), (ChatMessage,)>(
+            component.name(),
+            Some(&chat_config.component_handler),
+            &chat_config.component_handle_function,
+            (chat_config.provider, chat_config.model, messages),
+        )
         .await?;
+    info!("chat component call finished: {:?}", report);
 
-    let messages: Vec<Message> = results
-        .iter()
-        .filter_map(|r| {
-            if let Val::Record(fields) = r {
-                let role = fields
-                    .iter()
-                    .find(|(key, _)| key == "role") // Find field by name
-                    .and_then(|(_, val)| match val {
-                        Val::String(s) => Some(s.clone()), // Extract if it's a string
-                        _ => None,
-                    })?;
-
-                let content = fields.iter().find(|(key, _)| key == "content").and_then(
-                    |(_, val)| match val {
-                        Val::String(s) => Some(s.clone()),
-                        _ => None,
-                    },
-                )?;
-
-                Some(Message { role, content })
-            } else {
-                None // Skip if it's not a Record
-            }
-        })
-        .collect();
-
-    Ok(messages[0].clone())
+    Ok(Message { role: message.role, content: message.content })
 }
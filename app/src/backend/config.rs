@@ -11,6 +11,10 @@ pub struct Config {
 #[derive(Debug, Deserialize)]
 pub struct ChatConfig {
     pub component_wasm_path: String,
+    /// The sha256 of the reviewed component build, as a hex string. When set, any other binary
+    /// at `component_wasm_path` is refused.
+    #[serde(default)]
+    pub component_sha256: Option<String>,
     pub component_handler: String,
     pub component_handle_function: String,
     pub provider: String,
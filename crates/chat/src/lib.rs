@@ -25,7 +25,7 @@ impl From<ChatChoice> for Message {
 struct Component;
 
 impl Guest for Component {
-    fn handle(provider: String, model: String, apikey: String, messages: Vec<Message>) -> Message {
+    fn handle(provider: String, model: String, messages: Vec<Message>) -> Message {
         let endpoint = cloud_ai::get_chat_endpoint(&provider)
             .expect(&format!("{} is not supported", &provider));
 
@@ -39,12 +39,11 @@ impl Guest for Component {
 
         let body = serde_json::to_string(&chat_request_body).expect("Fail build json string");
 
-        // Build the wstd request
+        // Build the wstd request, the host attaches the provider credentials
         let req = Request::builder()
             .method("POST")
             .uri(endpoint)
             .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", apikey))
             .body(body.into_body())
             .expect("Fail to build chat request");
 
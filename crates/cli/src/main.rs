@@ -0,0 +1,324 @@
+//! `pawn`, a command-line runner for components.
+//!
+//! `pawn call` instantiates a component and calls one of its exports with JSON arguments, printing
+//! the results as JSON. `pawn inspect` lists what a component imports and exports. `pawn serve`
+//! serves HTTP requests with components exporting `wasi:http/incoming-handler`.
+
+use std::{collections::HashMap, net::SocketAddr, path::PathBuf, process::ExitCode, sync::Arc};
+
+use clap::{Args, Parser, Subcommand};
+use pawn_runtime::{
+    params_from_json, results_to_json, CapabilityPolicy, ComponentBuilder, ComponentError,
+    ComponentRegistry, ConfigError, HttpPolicy, HttpTrigger, HttpTriggerError, ItemDescriptor,
+    JsonError, Runtime, RuntimeConfig, RuntimeError, DEFAULT_COMPONENT_NAME,
+};
+use serde_json::Value;
+use snafu::{ResultExt, Snafu};
+use tokio::net::TcpListener;
+
+#[derive(Debug, Snafu)]
+enum CliError {
+    #[snafu(display("Failed to read {}: {}", path.display(), source))]
+    ReadWasmFailed { path: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Failed to create runtime: {}", source))]
+    RuntimeCreationFailed { source: RuntimeError },
+
+    #[snafu(display("{}", source))]
+    Component { source: ComponentError },
+
+    #[snafu(display("Invalid JSON arguments: {}", source))]
+    ArgumentsParseFailed { source: serde_json::Error },
+
+    #[snafu(display("Invalid arguments for '{}': {}", name, source))]
+    ArgumentsInvalid { name: String, source: JsonError },
+
+    #[snafu(display("Cannot print the results of '{}': {}", name, source))]
+    ResultsInvalid { name: String, source: JsonError },
+
+    #[snafu(display("Invalid environment variable '{}', expected KEY=VALUE", value))]
+    EnvInvalid { value: String },
+
+    #[snafu(display("Expected a function name, or an interface followed by a function name"))]
+    ExportInvalid,
+
+    #[snafu(display("{}", source))]
+    Config { source: ConfigError },
+
+    #[snafu(display("Invalid route '{}', expected PREFIX=WASM", value))]
+    RouteInvalid { value: String },
+
+    #[snafu(display(
+        "Components {} and {} are both named '{}'",
+        first.display(),
+        second.display(),
+        name
+    ))]
+    ComponentNameConflict { name: String, first: PathBuf, second: PathBuf },
+
+    #[snafu(display("{}", source))]
+    Trigger { source: HttpTriggerError },
+
+    #[snafu(display("Failed to listen on {}: {}", addr, source))]
+    BindFailed { addr: SocketAddr, source: std::io::Error },
+
+    #[snafu(display("Stopped accepting connections: {}", source))]
+    ServeFailed { source: std::io::Error },
+}
+
+type Result<T, E = CliError> = core::result::Result<T, E>;
+
+/// Runs WebAssembly components from the command line.
+#[derive(Debug, Parser)]
+#[command(name = "pawn", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Calls a function exported by a component, and prints its results as JSON.
+    Call(CallArgs),
+
+    /// Lists the imports and exports of a component.
+    Inspect(InspectArgs),
+
+    /// Serves HTTP requests with components exporting `wasi:http/incoming-handler`.
+    Serve(ServeArgs),
+}
+
+#[derive(Debug, Args)]
+struct CallArgs {
+    /// The WASM component to run.
+    wasm: PathBuf,
+
+    /// The function to call, optionally preceded by the interface exporting it, e.g.
+    /// `pawn:chat/handler handle`.
+    #[arg(required = true, num_args = 1..=2, value_names = ["INTERFACE", "FUNCTION"])]
+    export: Vec<String>,
+
+    /// The arguments, as a JSON array in parameter order or a JSON object keyed by parameter name.
+    #[arg(long, default_value = "[]")]
+    args: String,
+
+    #[command(flatten)]
+    capabilities: CapabilityArgs,
+}
+
+#[derive(Debug, Args)]
+struct ServeArgs {
+    /// Routes the requests under a path prefix to a component, as `PREFIX=WASM`, e.g.
+    /// `/hooks=hooks.wasm`. The longest matching prefix wins.
+    #[arg(long = "route", value_name = "PREFIX=WASM", required = true)]
+    routes: Vec<String>,
+
+    /// The address to listen on.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// A TOML runtime config. Its capability policies apply to components by file name, and are
+    /// extended by `--allow-http` and `--env`.
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    #[command(flatten)]
+    capabilities: CapabilityArgs,
+}
+
+#[derive(Debug, Args)]
+struct CapabilityArgs {
+    /// Allows outgoing HTTP requests to an authority, such as `api.openai.com`.
+    #[arg(long = "allow-http", value_name = "AUTHORITY")]
+    allow_http: Vec<String>,
+
+    /// Sets an environment variable of the components, as `KEY=VALUE`.
+    #[arg(long = "env", value_name = "KEY=VALUE")]
+    env: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+struct InspectArgs {
+    /// The WASM component to inspect.
+    wasm: PathBuf,
+
+    /// Prints the imports and exports as a WIT-style world.
+    #[arg(long)]
+    wit: bool,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Call(args) => call(args).await,
+        Command::Inspect(args) => inspect(args),
+        Command::Serve(args) => serve(args).await,
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Calls a function of a component and prints its results.
+async fn call(args: CallArgs) -> Result<()> {
+    let wasm = std::fs::read(&args.wasm).context(ReadWasmFailedSnafu { path: &args.wasm })?;
+    let runtime = Runtime::new().context(RuntimeCreationFailedSnafu)?;
+    let capabilities = args.capabilities.extend(CapabilityPolicy::default())?;
+
+    let component = ComponentBuilder::default()
+        .name(component_name(&args.wasm))
+        .runtime(&runtime)
+        .wasm(wasm.as_slice())
+        .capabilities(capabilities)
+        .build()
+        .context(ComponentSnafu)?;
+
+    let (namespace, function_name) = match args.export.as_slice() {
+        [function_name] => (None, function_name.as_str()),
+        [namespace, function_name] => (Some(namespace.as_str()), function_name.as_str()),
+        _ => return Err(CliError::ExportInvalid),
+    };
+    let (_, func) =
+        component.resolve_function_export(namespace, function_name).context(ComponentSnafu)?;
+    let json_args: Value = serde_json::from_str(&args.args).context(ArgumentsParseFailedSnafu)?;
+    let params = params_from_json(&func, &json_args)
+        .context(ArgumentsInvalidSnafu { name: function_name })?;
+
+    let (results, _) =
+        component.call(namespace, function_name, &params).await.context(ComponentSnafu)?;
+    let results = results_to_json(&results).context(ResultsInvalidSnafu { name: function_name })?;
+    println!("{}", serde_json::to_string_pretty(&results).unwrap_or_default());
+    Ok(())
+}
+
+/// Serves HTTP requests with the routed components until the process is stopped.
+async fn serve(args: ServeArgs) -> Result<()> {
+    let config = match &args.config {
+        Some(path) => RuntimeConfig::from_file(path).context(ConfigSnafu)?,
+        None => RuntimeConfig::default(),
+    };
+    let runtime = Runtime::from_config(&config).context(RuntimeCreationFailedSnafu)?;
+    let registry = Arc::new(ComponentRegistry::new(Arc::new(runtime)));
+    let mut trigger = HttpTrigger::new(registry.clone());
+
+    // Components are registered under their file name, once however many routes they serve
+    let mut registered: HashMap<String, PathBuf> = HashMap::new();
+    for route in &args.routes {
+        let (prefix, wasm_path) =
+            route.split_once('=').ok_or_else(|| CliError::RouteInvalid { value: route.clone() })?;
+        let wasm_path = PathBuf::from(wasm_path);
+        let name = component_name(&wasm_path);
+        match registered.get(&name) {
+            Some(first) if *first == wasm_path => {}
+            Some(first) => {
+                return Err(CliError::ComponentNameConflict {
+                    name,
+                    first: first.clone(),
+                    second: wasm_path,
+                })
+            }
+            None => {
+                let wasm =
+                    std::fs::read(&wasm_path).context(ReadWasmFailedSnafu { path: &wasm_path })?;
+                let policy = config.capability_policy(&name).context(ConfigSnafu)?;
+                let capabilities = args.capabilities.extend(policy)?;
+                registry
+                    .register(
+                        &name,
+                        ComponentBuilder::default()
+                            .wasm(wasm.as_slice())
+                            .capabilities(capabilities),
+                    )
+                    .context(ComponentSnafu)?;
+                registered.insert(name.clone(), wasm_path);
+            }
+        }
+        trigger.route(prefix, &name).context(TriggerSnafu)?;
+    }
+
+    let listener =
+        TcpListener::bind(args.listen).await.context(BindFailedSnafu { addr: args.listen })?;
+    for (prefix, name) in trigger.routes() {
+        eprintln!("{} -> {}", prefix, name);
+    }
+    Arc::new(trigger).serve(listener).await.context(ServeFailedSnafu)
+}
+
+/// Prints the imports and exports of a component.
+fn inspect(args: InspectArgs) -> Result<()> {
+    let wasm = std::fs::read(&args.wasm).context(ReadWasmFailedSnafu { path: &args.wasm })?;
+    let runtime = Runtime::new().context(RuntimeCreationFailedSnafu)?;
+    let component = ComponentBuilder::default()
+        .name(component_name(&args.wasm))
+        .runtime(&runtime)
+        .wasm(wasm.as_slice())
+        .build()
+        .context(ComponentSnafu)?;
+
+    if args.wit {
+        println!("{}", component.to_wit());
+        return Ok(());
+    }
+
+    println!("imports:");
+    for item in component.imports() {
+        print_item(&item, None);
+    }
+    println!("exports:");
+    for item in component.exports() {
+        print_item(&item, None);
+    }
+    Ok(())
+}
+
+/// Prints an item and, for interfaces, the functions it holds, one per line.
+///
+/// Items of nested interfaces are prefixed with the whole path of their interface, such as
+/// `pawn:agent/tools#search#query`.
+fn print_item(item: &ItemDescriptor, namespace: Option<&str>) {
+    match (item, namespace) {
+        (ItemDescriptor::Interface(interface), _) => {
+            let path = match namespace {
+                Some(namespace) => format!("{}#{}", namespace, interface.name),
+                None => interface.name.clone(),
+            };
+            for item in &interface.items {
+                print_item(item, Some(&path));
+            }
+        }
+        (ItemDescriptor::Function(function), Some(namespace)) => {
+            println!("  {}#{}", namespace, function)
+        }
+        (ItemDescriptor::Function(function), None) => println!("  {}", function),
+        (ItemDescriptor::Type { .. }, _) => {}
+        (item, Some(namespace)) => println!("  {}#{}", namespace, item.name()),
+        (item, None) => println!("  {}", item.name()),
+    }
+}
+
+impl CapabilityArgs {
+    /// Adds the environment variables and HTTP authorities of the arguments to a policy.
+    fn extend(&self, mut policy: CapabilityPolicy) -> Result<CapabilityPolicy> {
+        for value in &self.env {
+            let (key, value) = value
+                .split_once('=')
+                .ok_or_else(|| CliError::EnvInvalid { value: value.clone() })?;
+            policy.env.push((key.to_string(), value.to_string()));
+        }
+        policy.http.rules.extend(HttpPolicy::allow_authorities(&self.allow_http).rules);
+        Ok(policy)
+    }
+}
+
+/// Returns the name of a component, from the name of its file.
+fn component_name(path: &std::path::Path) -> String {
+    path.file_stem()
+        .map_or_else(|| DEFAULT_COMPONENT_NAME.to_string(), |s| s.to_string_lossy().into_owned())
+}
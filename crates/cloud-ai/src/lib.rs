@@ -11,3 +11,8 @@ pub fn get_chat_endpoint(provider: &str) -> Option<String> {
         _ => None,
     }
 }
+
+pub fn get_chat_authority(provider: &str) -> Option<String> {
+    let endpoint: http::Uri = get_chat_endpoint(provider)?.parse().ok()?;
+    endpoint.authority().map(ToString::to_string)
+}
@@ -0,0 +1,347 @@
+//! Admission control for component instances.
+//!
+//! Every call runs in its own instance, and the pooling allocator fails outright once all its
+//! slots are taken. The `AdmissionControl` of a runtime caps the number of instances in flight,
+//! both overall and for each component, and makes callers past a cap wait in a first-in,
+//! first-out queue until a slot frees up or their queue timeout expires.
+
+use core::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Instant,
+};
+
+use hashbrown::HashMap;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::warn;
+
+use crate::component::ComponentError;
+
+/// Default time a call may wait for admission before it is rejected (30 seconds)
+pub const ADMISSION_TIMEOUT: Duration = Duration::from_secs(30);
+
+type Result<T, E = ComponentError> = core::result::Result<T, E>;
+
+/// A snapshot of the admission counters of a runtime or of a component.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AdmissionStats {
+    /// The maximum number of instances in flight.
+    pub capacity: usize,
+
+    /// The number of instances currently in flight.
+    pub in_flight: usize,
+
+    /// The number of callers currently waiting for admission.
+    pub queued: usize,
+
+    /// The number of callers admitted so far.
+    pub admitted: u64,
+
+    /// The number of callers rejected because their queue timeout expired.
+    pub rejected: u64,
+
+    /// The total time admitted callers spent waiting.
+    pub total_wait: Duration,
+
+    /// The longest time an admitted caller spent waiting.
+    pub max_wait: Duration,
+}
+
+/// Caps the number of instances in flight, overall and per component.
+///
+/// Waiting callers are admitted in the order they arrived, as the underlying semaphores are fair.
+#[derive(Debug)]
+pub struct AdmissionControl {
+    /// The slots shared by all components.
+    global: Gate,
+
+    /// The slots of each component, created on its first admission.
+    components: Mutex<HashMap<String, Arc<Gate>>>,
+
+    /// The maximum number of instances in flight for a single component.
+    max_per_component: usize,
+
+    /// The maximum time a caller waits for admission.
+    timeout: Duration,
+}
+
+impl AdmissionControl {
+    /// Creates an admission control with the given caps.
+    ///
+    /// # Parameters
+    /// - `max_instances`: The maximum number of instances in flight across all components.
+    /// - `max_per_component`: The maximum number of instances in flight for a single component.
+    /// - `timeout`: The maximum time a caller waits for admission before it is rejected.
+    pub fn new(max_instances: usize, max_per_component: usize, timeout: Duration) -> Self {
+        Self {
+            global: Gate::new(max_instances),
+            components: Mutex::new(HashMap::new()),
+            max_per_component,
+            timeout,
+        }
+    }
+
+    /// Waits for a slot for a new instance of `component`.
+    ///
+    /// The caller first queues for a slot of the component, then for a global slot, so a busy
+    /// component does not hold global slots while it waits for its own. The slots are released
+    /// when the returned permit is dropped.
+    ///
+    /// # Parameters
+    /// - `component`: The name of the component to instantiate.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the permit to keep for as long as the instance lives.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::Overloaded`: If no slot freed up within the queue timeout.
+    pub async fn acquire(&self, component: &str) -> Result<AdmissionPermit> {
+        let gate = self.component_gate(component);
+        let started = Instant::now();
+        let permits = tokio::time::timeout(self.timeout, async {
+            let component_permit = gate.acquire().await;
+            let global_permit = self.global.acquire().await;
+            (component_permit, global_permit)
+        })
+        .await;
+
+        match permits {
+            Ok((component_permit, global_permit)) => {
+                let waited = started.elapsed();
+                gate.record_admission(waited);
+                self.global.record_admission(waited);
+                Ok(AdmissionPermit { _component: component_permit, _global: global_permit })
+            }
+            Err(_) => {
+                gate.rejected.fetch_add(1, Ordering::Relaxed);
+                self.global.rejected.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    "Rejected instance of component {} after waiting {:?} for admission",
+                    component, self.timeout
+                );
+                Err(ComponentError::Overloaded {
+                    name: component.to_string(),
+                    waited: self.timeout,
+                })
+            }
+        }
+    }
+
+    /// Returns the admission counters across all components.
+    pub fn stats(&self) -> AdmissionStats {
+        self.global.stats()
+    }
+
+    /// Returns the admission counters of `component`, or `None` if it was never admitted.
+    pub fn component_stats(&self, component: &str) -> Option<AdmissionStats> {
+        self.components
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(component)
+            .map(|gate| gate.stats())
+    }
+
+    /// Returns the slots of `component`, creating them if needed.
+    fn component_gate(&self, component: &str) -> Arc<Gate> {
+        self.components
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry_ref(component)
+            .or_insert_with(|| Arc::new(Gate::new(self.max_per_component)))
+            .clone()
+    }
+}
+
+impl Default for AdmissionControl {
+    /// Creates an admission control allowing as many instances as the default pooling allocator.
+    fn default() -> Self {
+        let max_instances = crate::runtime::MAX_COMPONENTS as usize;
+        Self::new(max_instances, max_instances, ADMISSION_TIMEOUT)
+    }
+}
+
+/// Holds the slots of an admitted instance, and releases them when dropped.
+#[derive(Debug)]
+pub struct AdmissionPermit {
+    /// The slot of the component.
+    _component: OwnedSemaphorePermit,
+
+    /// The global slot.
+    _global: OwnedSemaphorePermit,
+}
+
+/// A fair queue of callers in front of a fixed number of slots, with its counters.
+#[derive(Debug)]
+struct Gate {
+    /// The number of slots.
+    capacity: usize,
+
+    /// The free slots.
+    semaphore: Arc<Semaphore>,
+
+    /// The number of callers waiting for a slot.
+    queued: AtomicUsize,
+
+    /// The number of callers admitted.
+    admitted: AtomicU64,
+
+    /// The number of callers rejected.
+    rejected: AtomicU64,
+
+    /// The total time admitted callers waited, in nanoseconds.
+    total_wait: AtomicU64,
+
+    /// The longest time an admitted caller waited, in nanoseconds.
+    max_wait: AtomicU64,
+}
+
+impl Gate {
+    /// Creates a gate with `capacity` slots.
+    fn new(capacity: usize) -> Self {
+        let capacity = capacity.min(Semaphore::MAX_PERMITS);
+        Self {
+            capacity,
+            semaphore: Arc::new(Semaphore::new(capacity)),
+            queued: AtomicUsize::new(0),
+            admitted: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            total_wait: AtomicU64::new(0),
+            max_wait: AtomicU64::new(0),
+        }
+    }
+
+    /// Waits for a free slot, counting the caller as queued meanwhile.
+    async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        // Dropped when the wait ends, including when it is cancelled by the queue timeout
+        let _queued = QueuedGuard(&self.queued);
+        self.semaphore.clone().acquire_owned().await.expect("admission semaphores are never closed")
+    }
+
+    /// Counts an admission after waiting for `waited`.
+    fn record_admission(&self, waited: Duration) {
+        let nanos = u64::try_from(waited.as_nanos()).unwrap_or(u64::MAX);
+        self.admitted.fetch_add(1, Ordering::Relaxed);
+        self.total_wait.fetch_add(nanos, Ordering::Relaxed);
+        self.max_wait.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// Returns a snapshot of the counters.
+    fn stats(&self) -> AdmissionStats {
+        AdmissionStats {
+            capacity: self.capacity,
+            in_flight: self.capacity.saturating_sub(self.semaphore.available_permits()),
+            queued: self.queued.load(Ordering::Relaxed),
+            admitted: self.admitted.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            total_wait: Duration::from_nanos(self.total_wait.load(Ordering::Relaxed)),
+            max_wait: Duration::from_nanos(self.max_wait.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Decrements a queue depth when dropped.
+struct QueuedGuard<'a>(&'a AtomicUsize);
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmtime::component::Val;
+
+    use super::*;
+    use crate::{testing, ComponentBuilder};
+
+    /// Yields to the other tasks until `condition` holds.
+    async fn until(condition: impl Fn() -> bool) {
+        while !condition() {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn waiting_callers_are_admitted_in_order() {
+        let admission = Arc::new(AdmissionControl::new(1, 1, Duration::from_secs(5)));
+        let held = admission.acquire("a").await.unwrap();
+
+        let admitted = Arc::new(Mutex::new(Vec::new()));
+        let mut tasks = Vec::new();
+        for caller in 0..3 {
+            let (waiting, admitted) = (admission.clone(), admitted.clone());
+            tasks.push(tokio::spawn(async move {
+                let _permit = waiting.acquire("a").await.unwrap();
+                admitted.lock().unwrap().push(caller);
+            }));
+            until(|| admission.component_stats("a").unwrap().queued == caller + 1).await;
+        }
+
+        drop(held);
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(*admitted.lock().unwrap(), [0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn callers_past_the_timeout_are_rejected() {
+        let admission = AdmissionControl::new(2, 1, Duration::from_millis(20));
+        let _held = admission.acquire("a").await.unwrap();
+
+        let result = admission.acquire("a").await;
+        assert!(matches!(
+            result,
+            Err(ComponentError::Overloaded { ref name, waited }) if name == "a"
+                && waited == Duration::from_millis(20)
+        ));
+        // Other components still have slots
+        assert!(admission.acquire("b").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn stats_count_admissions_rejections_and_slots() {
+        let admission = AdmissionControl::new(2, 1, Duration::from_millis(20));
+        let held = admission.acquire("a").await.unwrap();
+        assert!(admission.acquire("a").await.is_err());
+
+        let stats = admission.component_stats("a").unwrap();
+        assert_eq!((stats.capacity, stats.in_flight, stats.queued), (1, 1, 0));
+        assert_eq!((stats.admitted, stats.rejected), (1, 1));
+        let stats = admission.stats();
+        assert_eq!((stats.capacity, stats.in_flight, stats.admitted, stats.rejected), (2, 1, 1, 1));
+
+        drop(held);
+        assert_eq!(admission.stats().in_flight, 0);
+        assert_eq!(admission.component_stats("b"), None);
+    }
+
+    #[tokio::test]
+    async fn components_built_directly_are_admitted() {
+        let runtime = testing::runtime_builder()
+            .max_concurrent_instances(1usize)
+            .admission_timeout(Duration::from_millis(20))
+            .build()
+            .unwrap();
+        let wasm = testing::wasm(testing::ADD);
+        let build = || ComponentBuilder::default().wasm(&*wasm).runtime(&runtime).build().unwrap();
+
+        let mut held = build();
+        held._component_export_function_lookup(None, "add").await.unwrap();
+        let result = build().call(None, "add", &[Val::U32(1), Val::U32(2)]).await;
+        assert!(matches!(result, Err(ComponentError::Overloaded { .. })), "{:?}", result);
+
+        drop(held);
+        let (results, _) = build().call(None, "add", &[Val::U32(1), Val::U32(2)]).await.unwrap();
+        assert_eq!(results, [Val::U32(3)]);
+        let stats = runtime.admission.stats();
+        assert_eq!((stats.in_flight, stats.admitted, stats.rejected), (0, 2, 1));
+    }
+}
@@ -0,0 +1,182 @@
+//! A persistent, content-addressed cache of compiled components.
+//!
+//! Compiling a component with Cranelift is expensive, so the runtime can keep the compiled
+//! artifacts on disk and reuse them across process restarts. Artifacts are keyed by the sha256 of
+//! the WASM binary together with the engine's compilation settings, so changing the engine
+//! configuration or the Wasmtime version never loads a stale artifact.
+
+use core::{
+    fmt::Write,
+    hash::{Hash, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+use tracing::warn;
+use wasmtime::{component::Component as WasmComponent, Engine};
+
+/// The file extension used for precompiled components.
+pub const PRECOMPILED_EXTENSION: &str = "cwasm";
+
+/// A directory of compiled components, keyed by the content of the WASM binary and the engine
+/// configuration they were compiled with.
+///
+/// The cache directory must only be writable by the runtime itself: loading an artifact skips
+/// compilation and validation entirely, so a tampered artifact can execute arbitrary code.
+#[derive(Debug, Clone)]
+pub struct ComponentCache {
+    /// The directory holding the compiled artifacts.
+    dir: PathBuf,
+}
+
+impl ComponentCache {
+    /// Creates a cache backed by the given directory. The directory is created on first write.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns the directory holding the compiled artifacts.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the cache key of a WASM binary compiled by the given engine, as a hex string.
+    pub fn key(engine: &Engine, wasm: &[u8]) -> String {
+        let mut hasher = Sha256Hasher(Sha256::new());
+        hasher.0.update(wasm);
+        engine.precompile_compatibility_hash().hash(&mut hasher);
+        hex(&hasher.0.finalize())
+    }
+
+    /// Returns the path of the artifact for a given cache key.
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(key).with_extension(PRECOMPILED_EXTENSION)
+    }
+
+    /// Loads the compiled component for a WASM binary from the cache, compiling and storing it if
+    /// it is missing.
+    ///
+    /// Unreadable artifacts are recompiled and failures to write the cache are logged, so the
+    /// cache never prevents a component from loading.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the compiled component, or the compilation error.
+    pub fn load_or_compile(&self, engine: &Engine, wasm: &[u8]) -> wasmtime::Result<WasmComponent> {
+        let path = self.path(&Self::key(engine, wasm));
+
+        if path.exists() {
+            // SAFETY: the artifact was serialized by `store` below into a directory that only the
+            // runtime writes to, and its key pins the engine configuration it was compiled with.
+            match unsafe { WasmComponent::deserialize_file(engine, &path) } {
+                Ok(component) => return Ok(component),
+                Err(e) => warn!("Ignoring unreadable cached component {}: {}", path.display(), e),
+            }
+        }
+
+        let component = WasmComponent::new(engine, wasm)?;
+        if let Err(e) = self.store(&path, &component) {
+            warn!("Failed to cache compiled component {}: {}", path.display(), e);
+        }
+        Ok(component)
+    }
+
+    /// Writes a compiled component to the cache. The artifact is written to a temporary file first
+    /// and then renamed, so concurrent readers never observe a partial artifact.
+    fn store(&self, path: &Path, component: &WasmComponent) -> wasmtime::Result<()> {
+        let bytes = component.serialize()?;
+        fs::create_dir_all(&self.dir)?;
+        let tmp = temp_path(path, PRECOMPILED_EXTENSION);
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Adapts a sha256 digest to `Hasher`, so values implementing `Hash` can be fed into it.
+struct Sha256Hasher(Sha256);
+
+impl Hasher for Sha256Hasher {
+    fn finish(&self) -> u64 {
+        let digest = self.0.clone().finalize();
+        u64::from_le_bytes(digest[..8].try_into().unwrap_or_default())
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+}
+
+/// Returns a temporary path next to `path` to write a file to before renaming it into place.
+///
+/// The name is unique across processes and threads, so concurrent writers of the same file never
+/// write to the same temporary file.
+pub(crate) fn temp_path(path: &Path, extension: &str) -> PathBuf {
+    static NEXT_TEMP_ID: AtomicU64 = AtomicU64::new(0);
+    let id = NEXT_TEMP_ID.fetch_add(1, Ordering::Relaxed);
+    path.with_extension(format!("{}.{}.{}.tmp", extension, std::process::id(), id))
+}
+
+/// Encodes bytes as a lowercase hex string.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        // Writing to a `String` never fails
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmtime::component::Val;
+
+    use super::*;
+    use crate::{testing, Component, ComponentError};
+
+    #[tokio::test]
+    async fn compiled_components_are_cached_per_engine() {
+        let dir = testing::TempDir::new("pawn-cache");
+        let runtime = testing::runtime_builder().cache_dir(dir.path()).build().unwrap();
+        let metered =
+            testing::runtime_builder().cache_dir(dir.path()).consume_fuel(true).build().unwrap();
+        let wasm = testing::wasm(testing::ADD);
+        let cache = runtime.cache().unwrap();
+        let path = cache.path(&ComponentCache::key(&runtime.engine, &wasm));
+        assert_ne!(path, cache.path(&ComponentCache::key(&metered.engine, &wasm)));
+
+        Component::with_runtime(&wasm, &runtime).unwrap();
+        assert!(path.exists());
+
+        // Unreadable artifacts are compiled again and replaced
+        fs::write(&path, b"not an artifact").unwrap();
+        let component = Component::with_runtime(&wasm, &runtime).unwrap();
+        let (results, _) = component.call(None, "add", &[Val::U32(1), Val::U32(2)]).await.unwrap();
+        assert_eq!(results, [Val::U32(3)]);
+        assert!(runtime.engine.detect_precompiled_file(&path).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn precompiled_artifacts_only_load_in_a_matching_engine() {
+        let runtime = testing::runtime_builder().build().unwrap();
+        let metered = testing::runtime_builder().consume_fuel(true).build().unwrap();
+        let wasm = testing::wasm(testing::ADD);
+        let artifact = Component::precompile(&wasm, &runtime).unwrap();
+
+        // SAFETY: the artifact was just compiled by this test
+        let component = unsafe { Component::from_precompiled(&artifact, &runtime) }.unwrap();
+        let (results, _) = component.call(None, "add", &[Val::U32(2), Val::U32(2)]).await.unwrap();
+        assert_eq!(results, [Val::U32(4)]);
+
+        // SAFETY: loading is expected to fail before any code runs
+        let result = unsafe { Component::from_precompiled(&artifact, &metered) };
+        assert!(matches!(result, Err(ComponentError::PrecompiledArtifactIncompatible { .. })));
+        // SAFETY: as above
+        let result = unsafe { Component::from_precompiled(&wasm, &runtime) };
+        assert!(matches!(result, Err(ComponentError::PrecompiledArtifactInvalid)));
+    }
+}
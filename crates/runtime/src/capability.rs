@@ -0,0 +1,162 @@
+//! Capabilities granted to a component through WASI.
+//!
+//! A component only sees the parts of the host that its [`CapabilityPolicy`] grants: environment
+//! variables, arguments, directories, standard streams, sockets and outgoing HTTP requests.
+//! Anything that is not granted is denied, so a policy built with `CapabilityPolicy::default()`
+//! isolates the component completely.
+
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use wasmtime_wasi::{DirPerms, FilePerms, WasiCtx, WasiCtxBuilder};
+
+use crate::{
+    http_policy::HttpPolicy,
+    output::{CapturedOutput, StreamKind},
+};
+
+/// Describes what a component may access on the host through WASI.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapabilityPolicy {
+    /// Environment variables visible to the component, as name and value pairs.
+    pub env: Vec<(String, String)>,
+
+    /// Command-line arguments visible to the component.
+    pub args: Vec<String>,
+
+    /// Host directories the component may access.
+    pub preopens: Vec<Preopen>,
+
+    /// Whether the component reads the host's standard input. When denied, the component sees an
+    /// empty input stream.
+    pub inherit_stdin: bool,
+
+    /// Where the component's standard output goes.
+    pub stdout: OutputPolicy,
+
+    /// Where the component's standard error goes.
+    pub stderr: OutputPolicy,
+
+    /// Whether the component may open TCP and UDP sockets through `wasi:sockets`.
+    pub allow_sockets: bool,
+
+    /// Whether the component may resolve host names through `wasi:sockets/ip-name-lookup`.
+    pub allow_ip_name_lookup: bool,
+
+    /// The outgoing HTTP requests the component may send through `wasi:http`.
+    pub http: HttpPolicy,
+}
+
+/// A host directory made available to a component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preopen {
+    /// The directory on the host.
+    pub host_path: PathBuf,
+
+    /// The path under which the component sees the directory.
+    pub guest_path: String,
+
+    /// What the component may do with the directory and its files.
+    pub access: DirAccess,
+}
+
+/// The access a component has to a preopened directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DirAccess {
+    /// The component may list the directory and read its files.
+    #[default]
+    ReadOnly,
+
+    /// The component may also create, modify and remove files and directories.
+    ReadWrite,
+}
+
+/// Where a standard output stream of a component goes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputPolicy {
+    /// The output is discarded.
+    Null,
+
+    /// The output is written to the host's corresponding stream.
+    Inherit,
+
+    /// Each line of output is emitted as a `tracing` event, and the last
+    /// [`MAX_CAPTURED_OUTPUT`] bytes are returned in the call report. Capturing does not give the
+    /// component access to the host, so it is the default.
+    ///
+    /// [`MAX_CAPTURED_OUTPUT`]: crate::MAX_CAPTURED_OUTPUT
+    #[default]
+    Capture,
+}
+
+impl CapabilityPolicy {
+    /// Builds the WASI context granting the capabilities of the policy.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the component, used to tag its captured output.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the WASI context and the captures of the component's output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a preopened directory cannot be opened.
+    pub(crate) fn build_ctx(&self, name: &str) -> wasmtime::Result<(WasiCtx, CapturedOutput)> {
+        let mut builder = WasiCtxBuilder::new();
+        builder.envs(&self.env).args(&self.args);
+
+        for preopen in &self.preopens {
+            let (dir_perms, file_perms) = match preopen.access {
+                DirAccess::ReadOnly => (DirPerms::READ, FilePerms::READ),
+                DirAccess::ReadWrite => (DirPerms::all(), FilePerms::all()),
+            };
+            builder.preopened_dir(
+                &preopen.host_path,
+                &preopen.guest_path,
+                dir_perms,
+                file_perms,
+            )?;
+        }
+
+        if self.inherit_stdin {
+            builder.inherit_stdin();
+        }
+        let mut captured = CapturedOutput::new(name);
+        match self.stdout {
+            OutputPolicy::Null => {}
+            OutputPolicy::Inherit => {
+                builder.inherit_stdout();
+            }
+            OutputPolicy::Capture => {
+                let capture = captured.capture(StreamKind::Stdout);
+                builder.stdout(capture.clone());
+                captured.stdout = Some(capture);
+            }
+        }
+        match self.stderr {
+            OutputPolicy::Null => {}
+            OutputPolicy::Inherit => {
+                builder.inherit_stderr();
+            }
+            OutputPolicy::Capture => {
+                let capture = captured.capture(StreamKind::Stderr);
+                builder.stderr(capture.clone());
+                captured.stderr = Some(capture);
+            }
+        }
+
+        // The default socket address check already denies every address, but TCP and UDP are
+        // disabled as well so the component sees a clear "access denied" instead
+        if self.allow_sockets {
+            builder.inherit_network();
+        } else {
+            builder.allow_tcp(false).allow_udp(false);
+        }
+        builder.allow_ip_name_lookup(self.allow_ip_name_lookup);
+
+        Ok((builder.build(), captured))
+    }
+}
@@ -1,33 +1,109 @@
 //! A module for interacting with WebAssembly (Wasm) components using the `wasmtime` runtime.
 //!
-//! This module provides a `Component` struct that encapsulates a Wasm component, its linker, and store,
-//! along with methods to instantiate and call functions within the component.
+//! This module provides a `Component` struct that encapsulates a Wasm component, its pre-instantiated
+//! form, and store, along with methods to instantiate and call functions within the component.
 
 // use alloc::string::{String, ToString};
 
-use derive_builder::Builder;
+use core::time::Duration;
+use std::{path::PathBuf, sync::Arc, time::Instant};
+
+use opentelemetry::{trace::SpanKind, Context, KeyValue};
+use sha2::{Digest, Sha256};
 use snafu::{prelude::*, ResultExt};
+use tracing::warn;
 use wasmtime::{
     component::{
-        Component as WasmComponent, ComponentType, Func, Instance, Linker, TypedFunc, Val,
+        types::{self, ComponentItem},
+        Component as WasmComponent, ComponentExportIndex, ComponentNamedList, Func, Instance,
+        InstancePre, Lift, Linker, Lower, Val,
     },
-    Engine, Store,
+    Engine, Precompiled, Store, Trap, WasmBacktrace, WasmCoreDump,
 };
 
-use crate::{state::State, Runtime};
+use crate::{
+    admission::AdmissionPermit,
+    cache::hex,
+    capability::CapabilityPolicy,
+    compose::{link_import, LinkedComponent},
+    coredump::write_coredump,
+    extension::HostExtension,
+    integrity::{Integrity, IntegrityError},
+    introspect::{ComponentDescriptor, ItemDescriptor},
+    limits::LimitExceeded,
+    registry::RegisteredComponent,
+    report::CallReport,
+    runtime::StoreSettings,
+    session::SessionEviction,
+    state::State,
+    telemetry::{self, CALL_ID, COMPONENT, FUNCTION},
+    validate::{ValidationProblem, Validator},
+    Runtime,
+};
 
 /// Enum to represent errors that can occur when working with Wasm components.
 #[derive(Debug, Snafu)]
 pub enum ComponentError {
+    #[snafu(display("Refused to load component '{}': {}", name, source))]
+    IntegrityCheckFailed { name: String, source: IntegrityError },
+
+    #[snafu(display(
+        "Component '{}' failed validation: {}",
+        name,
+        problems.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    ))]
+    ValidationFailed { name: String, problems: Vec<ValidationProblem> },
+
     #[snafu(display("Failed to create Wasm component: {}", source))]
     WasmComponentCreationFailed { source: wasmtime::Error },
 
     #[snafu(display("Failed to instantiate Wasm component: {}", source))]
     WasmComponentInstantiateFailed { source: wasmtime::Error },
 
+    #[snafu(display("Failed to grant capabilities to the Wasm component: {}", source))]
+    CapabilityGrantFailed { source: wasmtime::Error },
+
     #[snafu(display("Failed to link WASI: {}", source))]
     WasiLinkingFailed { source: wasmtime::Error },
 
+    #[snafu(display("Failed to link host extension '{}': {}", name, source))]
+    ExtensionLinkingFailed { name: String, source: wasmtime::Error },
+
+    #[snafu(display("Failed to initialize host extension '{}': {}", name, source))]
+    ExtensionInitFailed { name: String, source: wasmtime::Error },
+
+    #[snafu(display("Import '{}' not found", name))]
+    ImportNotFound { name: String },
+
+    #[snafu(display(
+        "Import '{}' is a {}, which cannot be linked to another component",
+        name,
+        kind
+    ))]
+    UnsupportedImport { name: String, kind: String },
+
+    #[snafu(display("Failed to link Wasm component imports: {}", source))]
+    ComponentLinkingFailed { source: wasmtime::Error },
+
+    #[snafu(display("Failed to precompile Wasm component: {}", source))]
+    PrecompileFailed { source: wasmtime::Error },
+
+    #[snafu(display("Artifact is not a precompiled Wasm component"))]
+    PrecompiledArtifactInvalid,
+
+    #[snafu(display("Precompiled artifact does not match this engine: {}", source))]
+    PrecompiledArtifactIncompatible { source: wasmtime::Error },
+
+    #[snafu(display(
+        "Component '{}' is overloaded, no instance slot freed up in {:?}",
+        name,
+        waited
+    ))]
+    Overloaded { name: String, waited: Duration },
+
+    #[snafu(display("Component '{}' is not registered", name))]
+    ComponentNotRegistered { name: String },
+
     #[snafu(display("Failed to read WASM file"))]
     ReadWasmFailed,
 
@@ -40,40 +116,84 @@ pub enum ComponentError {
     #[snafu(display("Exported function '{}' not found", name))]
     FunctionExportNotFound { name: String },
 
-    #[snafu(display("Failed to call function: {}", source))]
-    ComponentFunctionCallFailed { source: wasmtime::Error },
+    #[snafu(display(
+        "Failed to call function: {}{}{}{}",
+        source,
+        backtrace_suffix(wasm_backtrace),
+        coredump_suffix(coredump),
+        stderr_suffix(stderr)
+    ))]
+    ComponentFunctionCallFailed {
+        source: wasmtime::Error,
+        wasm_backtrace: Option<String>,
+        coredump: Option<PathBuf>,
+        stderr: Option<String>,
+    },
+
+    #[snafu(display("Signature of '{}' does not match the requested types: {}", name, source))]
+    FunctionSignatureMismatch { name: String, source: wasmtime::Error },
 
     #[snafu(display("Exported module '{}' not found", name))]
     ModuleExportNotFound { name: String },
 
     #[snafu(display("Exported  Handler '{}' not found", name))]
     HandlerExportNotFound { name: String },
+
+    #[snafu(display("Exported interface '{}' not found", name))]
+    InterfaceExportNotFound { name: String },
+
+    #[snafu(display("Export '{}' matches several versions: {}", name, candidates.join(", ")))]
+    AmbiguousExport { name: String, candidates: Vec<String> },
+
+    #[snafu(display("Export '{}' is a {}, which cannot be called", name, kind))]
+    UnsupportedExport { name: String, kind: String },
+
+    #[snafu(display("Execution of '{}' timed out after {:?}", name, elapsed))]
+    ExecutionTimeout { name: String, elapsed: Duration },
+
+    #[snafu(display("Execution of '{}' ran out of its fuel budget of {}", name, budget))]
+    FuelExhausted { name: String, budget: u64 },
+
+    #[snafu(display("Execution of '{}' exceeded a resource limit: {}", name, source))]
+    ResourceLimitExceeded { name: String, source: LimitExceeded },
+
+    #[snafu(display("Session of component '{}' needs an idle TTL greater than zero", name))]
+    SessionTtlInvalid { name: String },
+
+    #[snafu(display("Session of component '{}' was evicted: {}", name, reason))]
+    SessionExpired { name: String, reason: SessionEviction },
 }
 
 type Result<T, E = ComponentError> = core::result::Result<T, E>;
 
+/// Default name of a component that was built without one.
+pub const DEFAULT_COMPONENT_NAME: &str = "component";
+
 /// A struct representing a WebAssembly component.
 ///
-/// This struct encapsulates the Wasm component, its linker, store, and runtime, providing methods
-/// to interact with the component, such as calling exported functions.
-#[derive(Builder)]
-#[builder(no_std)]
-#[builder(setter(into))]
-#[builder(build_fn(skip))]
+/// This struct encapsulates the compiled and pre-linked component, its store, and runtime, providing
+/// methods to interact with the component, such as calling exported functions.
+///
+/// The component is admitted by the runtime's `AdmissionControl` before it is first instantiated,
+/// and keeps its slots until it is dropped, so components built directly count against the same
+/// caps as the calls of a `ComponentRegistry`.
 pub struct Component<'a> {
-    /// The Wasm component instance created from the WASM binary.
-    #[builder(setter(skip))]
-    component: WasmComponent,
+    /// The name identifying the component in logs and errors.
+    name: String,
 
-    /// The linker used to link the component with the WASI environment.
-    #[builder(setter(skip))]
-    linker: Linker<State>,
+    /// The compiled component, linked against the host interfaces.
+    pre: ComponentPre,
 
     /// The store in which the component and WASI environment are instantiated.
-    #[builder(setter(skip))]
     store: Store<State>,
 
-    /// The raw WASM binary.
+    /// The instance of the component in `store`, created when a function is first looked up.
+    instance: Option<Instance>,
+
+    /// The slots of the runtime the instance occupies, once admitted.
+    permit: Option<AdmissionPermit>,
+
+    /// The raw WASM binary, or a precompiled artifact.
     #[allow(dead_code)]
     wasm: &'a [u8],
 
@@ -81,7 +201,128 @@ pub struct Component<'a> {
     pub runtime: &'a Runtime,
 }
 
+/// Builder for [`Component`].
+///
+/// The WASM binary and the runtime are required. Everything else has a default: the component is
+/// named [`DEFAULT_COMPONENT_NAME`], is granted no capabilities, and has no extensions or links.
+#[derive(Clone, Default)]
+pub struct ComponentBuilder<'a> {
+    /// The name identifying the component in logs and errors.
+    name: Option<String>,
+
+    /// The raw WASM binary, or a precompiled artifact when `precompiled` is set.
+    wasm: Option<&'a [u8]>,
+
+    /// Whether `wasm` holds an artifact produced by [`Component::precompile`] rather than a WASM
+    /// binary.
+    precompiled: Option<bool>,
+
+    /// How the WASM binary is verified before it is loaded. Nothing is verified by default,
+    /// unless the runtime requires integrity.
+    integrity: Option<Integrity>,
+
+    /// The capabilities granted to the component through WASI. Nothing is granted by default.
+    capabilities: Option<CapabilityPolicy>,
+
+    /// The host extensions providing interfaces beyond WASI and `wasi:http`.
+    extensions: Option<Vec<Arc<dyn HostExtension>>>,
+
+    /// The imports backed by the exports of other components, as import names and providers.
+    links: Option<Vec<(String, ComponentPre)>>,
+
+    /// The fuel budget of each call, overriding the runtime's `fuel_limit`.
+    fuel_limit: Option<u64>,
+
+    /// The runtime compiling and running the component.
+    runtime: Option<&'a Runtime>,
+}
+
 impl<'a> ComponentBuilder<'a> {
+    /// Sets the name identifying the component in logs and errors.
+    pub fn name(&mut self, value: impl Into<String>) -> &mut Self {
+        self.name = Some(value.into());
+        self
+    }
+
+    /// Sets the WASM binary to compile, or the artifact to load when `precompiled` is set.
+    pub fn wasm(&mut self, value: impl Into<&'a [u8]>) -> &mut Self {
+        self.wasm = Some(value.into());
+        self
+    }
+
+    /// Sets how the WASM binary is verified before it is loaded.
+    pub fn integrity(&mut self, value: impl Into<Integrity>) -> &mut Self {
+        self.integrity = Some(value.into());
+        self
+    }
+
+    /// Sets the capabilities granted to the component through WASI.
+    pub fn capabilities(&mut self, value: impl Into<CapabilityPolicy>) -> &mut Self {
+        self.capabilities = Some(value.into());
+        self
+    }
+
+    /// Sets the fuel budget of each call of the component, overriding the runtime's `fuel_limit`.
+    ///
+    /// The budget only applies when the runtime has `consume_fuel` enabled. It also applies to the
+    /// calls forwarded to the component when it provides the import of another component.
+    pub fn fuel_limit(&mut self, value: u64) -> &mut Self {
+        self.fuel_limit = Some(value);
+        self
+    }
+
+    /// Sets the runtime compiling and running the component.
+    pub fn runtime(&mut self, value: impl Into<&'a Runtime>) -> &mut Self {
+        self.runtime = Some(value.into());
+        self
+    }
+
+    /// Marks `wasm` as an artifact produced by [`Component::precompile`], which is loaded without
+    /// compiling it.
+    ///
+    /// # Safety
+    ///
+    /// Loading a precompiled artifact skips validation of the machine code it contains. The
+    /// artifact must come from a trusted source, such as a build of the same deployment; loading
+    /// arbitrary bytes can lead to undefined behaviour. Artifacts built for a different engine
+    /// configuration are rejected.
+    pub unsafe fn precompiled(&mut self, value: bool) -> &mut Self {
+        self.precompiled = Some(value);
+        self
+    }
+
+    /// Adds a host extension, whose interfaces the component may import.
+    ///
+    /// Extensions are added to the linker in the order they are added to the builder.
+    pub fn extension(&mut self, extension: impl HostExtension) -> &mut Self {
+        self.extensions.get_or_insert_with(Vec::new).push(Arc::new(extension));
+        self
+    }
+
+    /// Links an imported interface to the same interface exported by another component.
+    ///
+    /// Calls the component makes to the functions of `interface` are forwarded to an instance of
+    /// `provider`, created in its own store on the first call. The provider keeps its own
+    /// capabilities, extensions and links, so components can be chained. Only functions and
+    /// types can be forwarded; interfaces with resources cannot be linked. Every function the
+    /// component imports must be exported by `provider` with the same type, which is checked when
+    /// the component is built. The provider runs under the admission permit of the component
+    /// rather than waiting for one of its own, and each forwarded call gets the fuel and epoch
+    /// deadline of a call of its own.
+    ///
+    /// # Parameters
+    /// - `interface`: The imported interface, such as `pawn:chat/handler`. A name without a
+    ///   version matches the single version the component imports.
+    /// - `provider`: The registered component exporting the interface.
+    pub fn link_import(
+        &mut self,
+        interface: impl Into<String>,
+        provider: &RegisteredComponent,
+    ) -> &mut Self {
+        self.links.get_or_insert_with(Vec::new).push((interface.into(), provider.pre().clone()));
+        self
+    }
+
     /// Builds the `Component` by configuring the runtime, WASM binary, and necessary dependencies such as the store
     /// and linker. If any of these are missing or incorrect, an error is returned.
     ///
@@ -90,24 +331,365 @@ impl<'a> ComponentBuilder<'a> {
     /// A `Result` containing either the created `Component` instance or an error if any part of the process fails.
     pub fn build(&self) -> Result<Component<'a>> {
         let runtime = self.runtime.ok_or(ComponentError::RuntimeSetFailed)?;
+        let (pre, wasm) = self.prepare(runtime)?;
+        pre.instantiate(wasm, runtime)
+    }
+
+    /// Checks the WASM binary before compiling it.
+    ///
+    /// The binary must fit in the runtime's `max_component_size`, be a valid component using only
+    /// the WebAssembly features the engine supports, start with memories that fit in its
+    /// `max_linear_memory`, and only import interfaces provided by WASI, `wasi:http`, the host
+    /// extensions or the linked components. Imports are not checked when an extension does not
+    /// list its interfaces. Precompiled artifacts are not checked.
+    ///
+    /// `build` runs this check before compiling, so calling it is only needed to check a component
+    /// without building it.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::RuntimeSetFailed`: If no runtime is set.
+    /// - `ComponentError::ReadWasmFailed`: If no WASM binary is set.
+    /// - `ComponentError::ValidationFailed`: With every problem found in the binary.
+    pub fn validate(&self) -> Result<()> {
+        let runtime = self.runtime.ok_or(ComponentError::RuntimeSetFailed)?;
+        self.validate_for(runtime)
+    }
+
+    /// Checks the WASM binary against the limits of `runtime`, which may differ from the runtime
+    /// set on the builder when a registry prepares the component.
+    fn validate_for(&self, runtime: &Runtime) -> Result<()> {
         let wasm = self.wasm.ok_or(ComponentError::ReadWasmFailed)?;
-        let engine = &runtime.engine;
+        if self.precompiled.unwrap_or(false) {
+            return Ok(());
+        }
+
+        let mut interfaces = Some(Vec::new());
+        for extension in self.extensions.iter().flatten() {
+            let provided = extension.interfaces();
+            if provided.is_empty() {
+                interfaces = None;
+                break;
+            }
+            interfaces.get_or_insert_with(Vec::new).extend(provided);
+        }
+        if let Some(interfaces) = &mut interfaces {
+            interfaces.extend(self.links.iter().flatten().map(|(name, _)| name.clone()));
+        }
+
+        let validator =
+            Validator::new(runtime.max_component_size(), runtime.max_linear_memory(), interfaces);
+        let problems = validator.validate(wasm);
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(ComponentError::ValidationFailed {
+            name: self.name.clone().unwrap_or_else(|| DEFAULT_COMPONENT_NAME.to_string()),
+            problems,
+        })
+    }
+
+    /// Compiles the WASM binary and links it against the host interfaces, without creating a store.
+    ///
+    /// The returned `ComponentPre` can be instantiated any number of times, which is much cheaper
+    /// than compiling the component again.
+    ///
+    /// # Parameters
+    /// - `runtime`: The runtime whose engine compiles the component.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the prepared component and the WASM binary it was compiled from.
+    pub(crate) fn prepare(&self, runtime: &Runtime) -> Result<(ComponentPre, &'a [u8])> {
+        let wasm = self.wasm.ok_or(ComponentError::ReadWasmFailed)?;
+        let name = self.name.clone().unwrap_or_else(|| DEFAULT_COMPONENT_NAME.to_string());
+        let hash: Arc<str> = hex(&Sha256::digest(wasm)).into();
+
+        // Refuse binaries that are not the reviewed build before anything parses them
+        self.integrity
+            .clone()
+            .unwrap_or_default()
+            .verify(wasm, &hash, &runtime.trusted_keys, runtime.require_integrity)
+            .context(IntegrityCheckFailedSnafu { name: &name })?;
 
-        // Create a new store with the provided engine
-        let state = State::new();
-        let store = Store::new(engine, state);
+        let capabilities = Arc::new(self.capabilities.clone().unwrap_or_default());
+        let engine = &runtime.engine;
 
         // Initialize the linker and add WASI support
         let mut linker = Linker::new(engine);
         wasmtime_wasi::add_to_linker_async(&mut linker).context(WasiLinkingFailedSnafu)?;
         wasmtime_wasi_http::add_only_http_to_linker_async(&mut linker)
             .context(WasiLinkingFailedSnafu)?;
+        let extensions: Arc<[Arc<dyn HostExtension>]> =
+            self.extensions.clone().unwrap_or_default().into();
+        for extension in extensions.iter() {
+            extension
+                .add_to_linker(&mut linker)
+                .context(ExtensionLinkingFailedSnafu { name: extension.name() })?;
+        }
+
+        // Reject binaries that cannot work before spending time compiling them
+        self.validate_for(runtime)?;
 
-        // Create the component from the WASM binary
-        let component =
-            WasmComponent::new(engine, wasm).context(WasmComponentCreationFailedSnafu)?;
+        // Create the component from the WASM binary, reusing compiled artifacts when possible
+        let started = Instant::now();
+        let cx = telemetry::start_span(
+            "pawn.compile",
+            SpanKind::Internal,
+            vec![KeyValue::new(COMPONENT, name.clone())],
+            &Context::current(),
+        );
+        let component = self.compile(runtime, wasm);
+        telemetry::end_span(&cx, started, component.as_ref().err().map(|e| e as _));
+        let component = component?;
+
+        // Forward the imports linked to other components
+        let imports: Vec<(String, ComponentItem)> = component
+            .component_type()
+            .imports(engine)
+            .map(|(name, item)| (name.to_string(), item))
+            .collect();
+        let mut links = Vec::new();
+        for (interface, provider) in self.links.iter().flatten() {
+            let import = match_export_name(&imports, interface).map_err(|e| match e {
+                ComponentError::InterfaceExportNotFound { name } => {
+                    ComponentError::ImportNotFound { name }
+                }
+                e => e,
+            })?;
+            link_import(&mut linker, &component, &import, provider)?;
+            links.push((import, provider.clone()));
+        }
 
-        Ok(Component { component, linker, store, wasm, runtime })
+        // Resolve the imports once, so instantiation only has to create a store
+        let instance_pre =
+            linker.instantiate_pre(&component).context(ComponentLinkingFailedSnafu)?;
+
+        Ok((
+            ComponentPre {
+                name,
+                hash,
+                component,
+                instance_pre,
+                capabilities,
+                extensions,
+                links: links.into(),
+                fuel_limit: self.fuel_limit,
+            },
+            wasm,
+        ))
+    }
+
+    /// Creates the Wasm component from the WASM binary, loading it from the precompiled artifact or
+    /// the runtime's cache when possible.
+    fn compile(&self, runtime: &Runtime, wasm: &[u8]) -> Result<WasmComponent> {
+        let engine = &runtime.engine;
+        if self.precompiled.unwrap_or(false) {
+            load_precompiled(engine, wasm)
+        } else if let Some(cache) = runtime.cache() {
+            cache.load_or_compile(engine, wasm).context(WasmComponentCreationFailedSnafu)
+        } else {
+            WasmComponent::new(engine, wasm).context(WasmComponentCreationFailedSnafu)
+        }
+    }
+}
+
+/// A compiled component whose imports are already resolved against the host interfaces.
+///
+/// Cloning is cheap, since the compiled code is shared between clones.
+#[derive(Clone)]
+pub(crate) struct ComponentPre {
+    /// The name identifying the component in logs and errors.
+    name: String,
+
+    /// The sha256 of the WASM binary, as a hex string.
+    hash: Arc<str>,
+
+    /// The Wasm component created from the WASM binary.
+    component: WasmComponent,
+
+    /// The pre-instantiated component, ready to be instantiated in a new store.
+    instance_pre: InstancePre<State>,
+
+    /// The capabilities granted to every instance of the component.
+    capabilities: Arc<CapabilityPolicy>,
+
+    /// The host extensions the component is linked with.
+    extensions: Arc<[Arc<dyn HostExtension>]>,
+
+    /// The imports backed by other components, as exact import names and providers.
+    links: Arc<[(String, ComponentPre)]>,
+
+    /// The fuel budget of each call, overriding the runtime's `fuel_limit`.
+    fuel_limit: Option<u64>,
+}
+
+impl ComponentPre {
+    /// Returns the name identifying the component.
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the sha256 of the WASM binary the component was compiled from, as a hex string.
+    pub(crate) fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    /// Describes the imports and exports of the component.
+    pub(crate) fn describe(&self) -> ComponentDescriptor {
+        ComponentDescriptor::new(&self.component)
+    }
+
+    /// Returns the fuel budget of each call of the component on `runtime`, or `None` if the
+    /// runtime does not consume fuel.
+    pub(crate) fn fuel_limit(&self, runtime: &Runtime) -> Option<u64> {
+        self.fuel_limit_within(runtime.consume_fuel.then_some(runtime.fuel_limit))
+    }
+
+    /// Returns the fuel budget of each call of the component, given the budget `runtime_limit` of
+    /// the runtime's calls, or `None` if the runtime does not consume fuel.
+    pub(crate) fn fuel_limit_within(&self, runtime_limit: Option<u64>) -> Option<u64> {
+        runtime_limit.map(|limit| self.fuel_limit.unwrap_or(limit))
+    }
+
+    /// Returns the pre-instantiated component.
+    pub(crate) fn instance_pre(&self) -> &InstancePre<State> {
+        &self.instance_pre
+    }
+
+    /// Creates a store for an instance of the component, with the runtime's execution limits.
+    ///
+    /// See [`ComponentPre::new_store_with`].
+    pub(crate) fn new_store(&self, runtime: &Runtime) -> Result<Store<State>> {
+        self.new_store_with(&runtime.store_settings())
+    }
+
+    /// Creates a store for an instance of the component, with the given execution limits.
+    ///
+    /// The components providing linked imports are kept in the state of the new store, and only
+    /// get a store of their own when the component first calls them.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::CapabilityGrantFailed`: If a capability of the policy cannot be granted,
+    ///   such as a preopened directory that does not exist.
+    /// - `ComponentError::ExtensionInitFailed`: If a host extension fails to prepare the state.
+    pub(crate) fn new_store_with(&self, settings: &StoreSettings) -> Result<Store<State>> {
+        let mut state =
+            State::new(&self.name, self.capabilities.clone(), settings.secrets().clone())
+                .context(CapabilityGrantFailedSnafu)?;
+        for extension in self.extensions.iter() {
+            extension
+                .init_state(&mut state)
+                .context(ExtensionInitFailedSnafu { name: extension.name() })?;
+        }
+        for (import, provider) in self.links.iter() {
+            state.link(import.clone(), LinkedComponent::new(provider.clone(), settings.clone()));
+        }
+        Ok(settings.new_store(state))
+    }
+
+    /// Creates a `Component` with a fresh store, ready to be called.
+    ///
+    /// # Parameters
+    /// - `wasm`: The WASM binary the component was compiled from.
+    /// - `runtime`: The runtime that compiled the component.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Component`.
+    ///
+    /// # Errors
+    ///
+    /// - Any error returned by [`ComponentPre::new_store`].
+    pub(crate) fn instantiate<'a>(
+        &self,
+        wasm: &'a [u8],
+        runtime: &'a Runtime,
+    ) -> Result<Component<'a>> {
+        let store = self.new_store(runtime)?;
+
+        Ok(Component {
+            name: self.name.clone(),
+            pre: self.clone(),
+            store,
+            instance: None,
+            permit: None,
+            wasm,
+            runtime,
+        })
+    }
+
+    /// Resolves an exported function to its export index and type, without instantiating the
+    /// component.
+    ///
+    /// `namespace` is the name of the exported instance holding the function, such as
+    /// `pawn:chat/handler` or `pawn:chat/handler@0.1.0`. A name without a version matches the
+    /// single version the component exports. Nested instances are separated by `#`, for example
+    /// `pawn:agent/tools#search`. Without a namespace, `function_name` must be a function exported
+    /// at the top level of the component.
+    ///
+    /// # Parameters
+    /// - `namespace`: Optional path of the instance exporting the function.
+    /// - `function_name`: The exact name of the exported function.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the export index and type of the function.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::InterfaceExportNotFound`: If an instance on the path is not exported.
+    /// - `ComponentError::AmbiguousExport`: If an unversioned name matches several versions.
+    /// - `ComponentError::FunctionExportNotFound`: If the function is not exported by the instance.
+    /// - `ComponentError::UnsupportedExport`: If the export is not a component function.
+    pub(crate) fn resolve_function_export(
+        &self,
+        namespace: Option<&str>,
+        function_name: &str,
+    ) -> Result<(ComponentExportIndex, types::ComponentFunc)> {
+        let component = &self.component;
+        let engine = component.engine();
+        let mut exports: Vec<(String, ComponentItem)> = component
+            .component_type()
+            .exports(engine)
+            .map(|(name, item)| (name.to_string(), item))
+            .collect();
+        let mut parent: Option<ComponentExportIndex> = None;
+
+        // Walk down the exported instances named by the namespace
+        let mut path = String::new();
+        for segment in namespace.into_iter().flat_map(|namespace| namespace.split('#')) {
+            let name = match_export_name(&exports, segment)?;
+            if !path.is_empty() {
+                path.push('#');
+            }
+            path.push_str(&name);
+
+            let (item, index) = component
+                .export_index(parent.as_ref(), &name)
+                .ok_or_else(|| ComponentError::InterfaceExportNotFound { name: path.clone() })?;
+            let ComponentItem::ComponentInstance(instance) = item else {
+                return Err(ComponentError::UnsupportedExport {
+                    name: path,
+                    kind: item_kind(&item).to_string(),
+                });
+            };
+            exports =
+                instance.exports(engine).map(|(name, item)| (name.to_string(), item)).collect();
+            parent = Some(index);
+        }
+
+        let name = export_name(namespace, function_name);
+        let (item, index) = component
+            .export_index(parent.as_ref(), function_name)
+            .ok_or_else(|| ComponentError::FunctionExportNotFound { name: name.clone() })?;
+        match item {
+            ComponentItem::ComponentFunc(func) => Ok((index, func)),
+            item => {
+                Err(ComponentError::UnsupportedExport { name, kind: item_kind(&item).to_string() })
+            }
+        }
     }
 }
 
@@ -125,11 +707,98 @@ impl<'a> Component<'a> {
         ComponentBuilder::default().wasm(wasm).runtime(runtime).build()
     }
 
-    /// Looks up an exported function from the component by its name. This method allows calling
-    /// a specific function within the WebAssembly component.
+    /// Creates a new `Component` from an artifact produced by [`Component::precompile`].
     ///
     /// # Parameters
-    /// - `namespace`: Optional namespace to filter exported functions.
+    /// - `artifact`: The precompiled component, usually read from a `.cwasm` file.
+    /// - `runtime`: A reference to the runtime in which the component will be instantiated.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the created `Component` instance or an error if the artifact cannot
+    /// be loaded.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::PrecompiledArtifactInvalid`: If the bytes are not a precompiled component.
+    /// - `ComponentError::PrecompiledArtifactIncompatible`: If the artifact was built by an engine
+    ///   with a different configuration or Wasmtime version.
+    ///
+    /// # Safety
+    ///
+    /// See [`ComponentBuilder::precompiled`]: the artifact must come from a trusted source.
+    pub unsafe fn from_precompiled(
+        artifact: &'a [u8],
+        runtime: &'a Runtime,
+    ) -> Result<Component<'a>> {
+        ComponentBuilder::default().wasm(artifact).runtime(runtime).precompiled(true).build()
+    }
+
+    /// Compiles a WASM binary ahead of time into an artifact that can be written to a `.cwasm`
+    /// file and loaded back with [`Component::from_precompiled`].
+    ///
+    /// The artifact can only be loaded by a runtime with the same engine configuration.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::PrecompileFailed`: If the WASM binary fails to compile.
+    pub fn precompile(wasm: &[u8], runtime: &Runtime) -> Result<Vec<u8>> {
+        runtime.engine.precompile_component(wasm).context(PrecompileFailedSnafu)
+    }
+
+    /// Returns the name identifying the component.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the tree of interfaces, functions and types exported by the component.
+    pub fn exports(&self) -> Vec<ItemDescriptor> {
+        self.pre.describe().exports
+    }
+
+    /// Returns the tree of interfaces, functions and types the component imports from the host.
+    pub fn imports(&self) -> Vec<ItemDescriptor> {
+        self.pre.describe().imports
+    }
+
+    /// Describes the imports and exports of the component.
+    pub fn describe(&self) -> ComponentDescriptor {
+        self.pre.describe()
+    }
+
+    /// Renders the imports and exports of the component as a WIT-style world named after the
+    /// component.
+    pub fn to_wit(&self) -> String {
+        self.pre.describe().to_wit(&self.name)
+    }
+
+    /// Resolves an exported function to its export index and type, without instantiating the
+    /// component.
+    ///
+    /// See [`ComponentPre::resolve_function_export`] for how names are matched.
+    ///
+    /// # Parameters
+    /// - `namespace`: Optional path of the instance exporting the function.
+    /// - `function_name`: The exact name of the exported function.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the export index and type of the function.
+    pub fn resolve_function_export(
+        &self,
+        namespace: Option<&str>,
+        function_name: &str,
+    ) -> Result<(ComponentExportIndex, types::ComponentFunc)> {
+        self.pre.resolve_function_export(namespace, function_name)
+    }
+    /// Looks up an exported function from the component by its name, instantiating the component
+    /// in its store the first time a function is looked up. This method allows calling a specific
+    /// function within the WebAssembly component.
+    ///
+    /// See [`Component::resolve_function_export`] for how names are matched.
+    ///
+    /// # Parameters
+    /// - `namespace`: Optional path of the instance exporting the function.
     /// - `function_name`: The name of the exported function to look up.
     ///
     /// # Returns
@@ -138,69 +807,17 @@ impl<'a> Component<'a> {
     ///
     /// # Errors
     ///
-    /// - `ComponentError::FunctionExportNotFound`: If the function is not found.
+    /// - `ComponentError::Overloaded`: If the component could not be admitted within the admission
+    ///   timeout of the runtime.
+    /// - Any error returned by [`Component::resolve_function_export`].
     /// - `ComponentError::WasmComponentInstantiateFailed`: If the component instantiation fails.
     pub async fn _component_export_function_lookup(
         &mut self,
         namespace: Option<&str>,
         function_name: &str,
     ) -> Result<Func> {
-        let instance = self
-            .linker
-            .instantiate_async(&mut self.store, &self.component)
-            .await
-            .context(WasmComponentInstantiateFailedSnafu)?;
-        for (export_name, ty) in self.component.component_type().exports(&self.runtime.engine) {
-            if let Some(namespace) = namespace {
-                if !export_name.starts_with(namespace) {
-                    continue;
-                }
-            }
-            match ty {
-                wasmtime::component::types::ComponentItem::ComponentFunc(component_func) => {
-                    let (_, handle_export_index) = self
-                        .component
-                        .export_index(None, export_name)
-                        .ok_or(
-                        ComponentError::FunctionExportNotFound { name: function_name.to_string() },
-                    )?;
-
-                    let func = instance.get_func(&mut self.store, handle_export_index).ok_or(
-                        ComponentError::FunctionExportNotFound { name: function_name.to_string() },
-                    )?;
-
-                    return Ok(func);
-                }
-                wasmtime::component::types::ComponentItem::CoreFunc(func_type) => todo!(),
-                wasmtime::component::types::ComponentItem::Module(module) => todo!(),
-                wasmtime::component::types::ComponentItem::Component(component) => todo!(),
-                wasmtime::component::types::ComponentItem::ComponentInstance(
-                    component_instance,
-                ) => {
-                    let (_, handler_export_index) = self
-                        .component
-                        .export_index(None, export_name)
-                        .ok_or(ComponentError::ModuleExportNotFound {
-                            name: function_name.to_string(),
-                        })?;
-                    let (_, handle_export_index) = self
-                        .component
-                        .export_index(Some(&handler_export_index), "handle")
-                        .ok_or(ComponentError::FunctionExportNotFound {
-                            name: function_name.to_string(),
-                        })?;
-                    let func = instance.get_func(&mut self.store, handle_export_index).ok_or(
-                        ComponentError::FunctionExportNotFound { name: function_name.to_string() },
-                    )?;
-
-                    return Ok(func);
-                }
-                wasmtime::component::types::ComponentItem::Type(_) => todo!(),
-                wasmtime::component::types::ComponentItem::Resource(resource_type) => todo!(),
-            }
-        }
-
-        Err(ComponentError::FunctionExportNotFound { name: function_name.to_string() })
+        self.admit().await?;
+        self.call_context().function(namespace, function_name).await
     }
 
     /// Calls the specified function from the component with the given parameters.
@@ -209,31 +826,508 @@ impl<'a> Component<'a> {
     /// - `namespace`: Optional namespace to filter exported functions.
     /// - `function_name`: The name of the function to call within the WebAssembly component.
     /// - `params`: The parameters to pass to the function when calling it.
-    /// - `results`: Mutable slice to store function results.
     ///
     /// # Returns
     ///
-    /// A `Result` indicating success or failure.
+    /// A `Result` containing the function results and a `CallReport` describing the resources
+    /// the call used.
     ///
     /// # Errors
     ///
-    /// - `ComponentError::ComponentFunctionCallFailed`: If the function call fails.
+    /// - `ComponentError::Overloaded`: If the component could not be admitted within the admission
+    ///   timeout of the runtime.
+    /// - `ComponentError::ComponentFunctionCallFailed`: If the function call fails. The error
+    ///   carries the end of the component's standard error, when it is captured. When the guest
+    ///   traps, it also carries the symbolized backtrace and, if the runtime has a
+    ///   `coredump_dir`, the path of the coredump written for the call.
+    /// - `ComponentError::ExecutionTimeout`: If the call runs past the runtime's `max_execution_time`.
+    /// - `ComponentError::FuelExhausted`: If the call consumes its whole fuel budget, the
+    ///   component's `fuel_limit` or else the runtime's.
+    /// - `ComponentError::ResourceLimitExceeded`: If the memories of the component grow past the
+    ///   runtime's `max_linear_memory` in total, or a table grows past its `table_elements`.
     /// - `ComponentError::FunctionExportNotFound`: If the exported function is not found.
-    /// - `ComponentError::WasmComponentInstantiateFailed`: if component instantiation
+    /// - `ComponentError::WasmComponentInstantiateFailed`: if component instantiation fails, which
+    ///   includes creating more instances, memories or tables than the runtime allows.
     pub async fn call(
         mut self,
         namespace: Option<&str>,
         function_name: &str,
         params: &[Val],
-    ) -> Result<Vec<Val>> {
-        let func = self._component_export_function_lookup(namespace, function_name).await?;
-        let num_results = func.results(&self.store).len();
+    ) -> Result<(Vec<Val>, CallReport)> {
+        self.admit().await?;
+        self.call_context().call(namespace, function_name, params).await
+    }
+
+    /// Calls the specified function from the component with statically typed parameters and
+    /// results.
+    ///
+    /// The Rust types are checked against the function's WIT signature before the function is
+    /// called. The component is instantiated by then, so its start functions have run. Records, variants, enums and flags can be mapped to Rust
+    /// types with the `ComponentType`, `Lift` and `Lower` derives of `wasmtime::component`.
+    ///
+    /// # Parameters
+    /// - `namespace`: Optional path of the instance exporting the function.
+    /// - `function_name`: The name of the function to call within the WebAssembly component.
+    /// - `params`: The parameters to pass to the function, as a tuple.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the function results, as a tuple, and a `CallReport` describing the
+    /// resources the call used.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::FunctionSignatureMismatch`: If `Params` or `Results` do not match the
+    ///   function's signature.
+    /// - Any error returned by [`Component::call`].
+    pub async fn call_typed<Params, Results>(
+        mut self,
+        namespace: Option<&str>,
+        function_name: &str,
+        params: Params,
+    ) -> Result<(Results, CallReport)>
+    where
+        Params: ComponentNamedList + Lower + Send + Sync,
+        Results: ComponentNamedList + Lift + Send + Sync,
+    {
+        self.admit().await?;
+        self.call_context().call_typed(namespace, function_name, params).await
+    }
+
+    /// Waits for the runtime to admit the instance of the component, unless it was admitted
+    /// already.
+    async fn admit(&mut self) -> Result<()> {
+        if self.permit.is_none() {
+            self.permit = Some(self.runtime.admission.acquire(&self.name).await?);
+        }
+        Ok(())
+    }
+
+    /// Returns the context in which calls run against the store of the component.
+    fn call_context(&mut self) -> CallContext<'_> {
+        CallContext {
+            name: &self.name,
+            pre: &self.pre,
+            store: &mut self.store,
+            instance: &mut self.instance,
+            runtime: self.runtime,
+        }
+    }
+}
+
+/// A store and the instance of a component living in it, against which calls run.
+///
+/// A `Component` is called once, while a `Session` calls the same instance until it is evicted,
+/// so both drive their calls through this context.
+pub(crate) struct CallContext<'s> {
+    /// The name identifying the component in logs and errors.
+    pub(crate) name: &'s str,
+
+    /// The compiled component, linked against the host interfaces.
+    pub(crate) pre: &'s ComponentPre,
+
+    /// The store in which the component is instantiated.
+    pub(crate) store: &'s mut Store<State>,
+
+    /// The instance of the component, created in the store by the first call.
+    pub(crate) instance: &'s mut Option<Instance>,
+
+    /// The runtime whose limits apply to the calls.
+    pub(crate) runtime: &'s Runtime,
+}
+
+impl CallContext<'_> {
+    /// Looks up an exported function, instantiating the component in the store if no call did yet.
+    ///
+    /// # Errors
+    ///
+    /// - Any error returned by [`ComponentPre::resolve_function_export`].
+    /// - `ComponentError::WasmComponentInstantiateFailed`: If the component instantiation fails.
+    pub(crate) async fn function(
+        &mut self,
+        namespace: Option<&str>,
+        function_name: &str,
+    ) -> Result<Func> {
+        let (index, _) = self.pre.resolve_function_export(namespace, function_name)?;
+        let instance = match *self.instance {
+            Some(instance) => instance,
+            None => {
+                let started = Instant::now();
+                let cx = telemetry::start_span(
+                    "pawn.instantiate",
+                    SpanKind::Internal,
+                    vec![KeyValue::new(COMPONENT, self.name.to_string())],
+                    self.store.data().trace_context(),
+                );
+                let instance = self.pre.instance_pre.instantiate_async(&mut *self.store).await;
+                telemetry::end_span(&cx, started, instance.as_ref().err().map(|e| e as _));
+                *self.instance.insert(instance.context(WasmComponentInstantiateFailedSnafu)?)
+            }
+        };
+
+        instance.get_func(&mut *self.store, index).ok_or_else(|| {
+            ComponentError::FunctionExportNotFound { name: export_name(namespace, function_name) }
+        })
+    }
+
+    /// Calls a function with the given parameters. See [`Component::call`].
+    pub(crate) async fn call(
+        &mut self,
+        namespace: Option<&str>,
+        function_name: &str,
+        params: &[Val],
+    ) -> Result<(Vec<Val>, CallReport)> {
+        let (func, start) = self.start_call(namespace, function_name).await?;
+        let num_results = func.results(&*self.store).len();
         let mut results = vec![Val::Option(None); num_results];
 
         // Call the function with the provided parameters
-        func.call_async(&mut self.store, params, &mut results)
-            .await
-            .map_err(|e| ComponentError::ComponentFunctionCallFailed { source: e })?;
-        Ok(results)
+        if let Err(source) = func.call_async(&mut *self.store, params, &mut results).await {
+            return Err(self.call_error(source, namespace, function_name, &start));
+        }
+        // Let the instance be entered again by the next call of a session
+        if let Err(source) = func.post_return_async(&mut *self.store).await {
+            return Err(self.call_error(source, namespace, function_name, &start));
+        }
+
+        Ok((results, self.finish_call(start)))
+    }
+
+    /// Calls a function with statically typed parameters and results. See
+    /// [`Component::call_typed`].
+    pub(crate) async fn call_typed<Params, Results>(
+        &mut self,
+        namespace: Option<&str>,
+        function_name: &str,
+        params: Params,
+    ) -> Result<(Results, CallReport)>
+    where
+        Params: ComponentNamedList + Lower + Send + Sync,
+        Results: ComponentNamedList + Lift + Send + Sync,
+    {
+        let (func, start) = self.start_call(namespace, function_name).await?;
+        let func = match func.typed::<Params, Results>(&*self.store) {
+            Ok(func) => func,
+            Err(source) => {
+                let name = export_name(namespace, function_name);
+                let error = ComponentError::FunctionSignatureMismatch { name, source };
+                return Err(self.abort_call(error, &start));
+            }
+        };
+
+        // Call the function with the provided parameters
+        let results = match func.call_async(&mut *self.store, params).await {
+            Ok(results) => results,
+            Err(source) => return Err(self.call_error(source, namespace, function_name, &start)),
+        };
+        if let Err(source) = func.post_return_async(&mut *self.store).await {
+            return Err(self.call_error(source, namespace, function_name, &start));
+        }
+
+        Ok((results, self.finish_call(start)))
+    }
+
+    /// Resets the store's execution limits, starts the span of the call and looks up the function
+    /// to call.
+    ///
+    /// The deadline and fuel budget cover both instantiation and the call itself. The span is a
+    /// child of the current OpenTelemetry context, and is ended when the call finishes or fails.
+    async fn start_call(
+        &mut self,
+        namespace: Option<&str>,
+        function_name: &str,
+    ) -> Result<(Func, CallStart)> {
+        self.store.set_epoch_deadline(self.runtime.epoch_deadline_ticks());
+        if let Some(fuel) = self.pre.fuel_limit(self.runtime) {
+            // Only fails when fuel is disabled in the engine config
+            let _ = self.store.set_fuel(fuel);
+        }
+        let start = CallStart {
+            call_id: self.store.data_mut().begin_call(),
+            started: Instant::now(),
+            fuel_before: self.store.get_fuel().ok(),
+        };
+        let cx = telemetry::start_span(
+            "pawn.call",
+            SpanKind::Internal,
+            vec![
+                KeyValue::new(COMPONENT, self.name.to_string()),
+                KeyValue::new(FUNCTION, export_name(namespace, function_name)),
+                KeyValue::new(CALL_ID, start.call_id as i64),
+            ],
+            &Context::current(),
+        );
+        self.store.data_mut().set_trace_context(cx);
+
+        let error = match self.function(namespace, function_name).await {
+            Ok(func) => return Ok((func, start)),
+            Err(ComponentError::WasmComponentInstantiateFailed { source }) => self
+                .limit_error(&source, namespace, function_name, start.started)
+                .unwrap_or(ComponentError::WasmComponentInstantiateFailed { source }),
+            Err(e) => e,
+        };
+        Err(self.abort_call(error, &start))
+    }
+
+    /// Builds the report of a call that started at `start`.
+    fn finish_call(&mut self, start: CallStart) -> CallReport {
+        self.store.data().end_call();
+        telemetry::end_span(self.store.data().trace_context(), start.started, None);
+        CallReport {
+            call_id: start.call_id,
+            fuel_consumed: start
+                .fuel_before
+                .zip(self.store.get_fuel().ok())
+                .map(|(before, after)| before.saturating_sub(after)),
+            peak_memory: self.store.data_mut().limiter().peak_memory(),
+            wall_time: start.started.elapsed(),
+            outgoing_requests: self.store.data().outgoing_requests(),
+            stdout: self.store.data().captured_stdout(),
+            stderr: self.store.data().captured_stderr(),
+        }
+    }
+
+    /// Ends a call that failed before the function was called, and returns `error`.
+    fn abort_call(&mut self, error: ComponentError, start: &CallStart) -> ComponentError {
+        self.store.data().end_call();
+        telemetry::end_span(self.store.data().trace_context(), start.started, Some(&error));
+        error
+    }
+
+    /// Ends a call that failed with `source`, and returns the matching `ComponentError`.
+    fn call_error(
+        &mut self,
+        source: wasmtime::Error,
+        namespace: Option<&str>,
+        function_name: &str,
+        start: &CallStart,
+    ) -> ComponentError {
+        self.store.data().end_call();
+        let error = self.failed_call_error(source, namespace, function_name, start);
+        telemetry::end_span(self.store.data().trace_context(), start.started, Some(&error));
+        error
+    }
+
+    /// Returns the `ComponentError` of a call that failed with `source`.
+    fn failed_call_error(
+        &mut self,
+        source: wasmtime::Error,
+        namespace: Option<&str>,
+        function_name: &str,
+        start: &CallStart,
+    ) -> ComponentError {
+        if let Some(error) = self.limit_error(&source, namespace, function_name, start.started) {
+            return error;
+        }
+
+        // Report traps by their code, with the backtrace and coredump apart
+        let Some(&trap) = source.downcast_ref::<Trap>() else {
+            return ComponentError::ComponentFunctionCallFailed {
+                source,
+                wasm_backtrace: None,
+                coredump: None,
+                stderr: self.store.data().stderr_tail(),
+            };
+        };
+        let wasm_backtrace = source.downcast_ref::<WasmBacktrace>().map(ToString::to_string);
+        let coredump = source
+            .downcast_ref::<WasmCoreDump>()
+            .and_then(|dump| self.write_coredump(dump, start.call_id));
+        ComponentError::ComponentFunctionCallFailed {
+            source: trap.into(),
+            wasm_backtrace,
+            coredump,
+            stderr: self.store.data().stderr_tail(),
+        }
+    }
+
+    /// Writes the coredump of a trapped call to the runtime's `coredump_dir`, if it is set.
+    ///
+    /// Failures are logged rather than returned, so they never hide the trap itself.
+    fn write_coredump(&mut self, dump: &WasmCoreDump, call_id: u64) -> Option<PathBuf> {
+        let dir = self.runtime.coredump_dir.as_ref()?;
+        match write_coredump(dir, &mut self.store, dump, self.pre.hash(), call_id) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                warn!("Failed to write coredump of component {}: {}", self.name, e);
+                None
+            }
+        }
+    }
+
+    /// Returns the `ComponentError` for an error raised while running guest code if it is caused
+    /// by one of the runtime's execution or resource limits.
+    fn limit_error(
+        &self,
+        error: &wasmtime::Error,
+        namespace: Option<&str>,
+        function_name: &str,
+        started: Instant,
+    ) -> Option<ComponentError> {
+        let exceeded = error.chain().find_map(|cause| cause.downcast_ref::<LimitExceeded>());
+        if let Some(source) = exceeded.cloned() {
+            return Some(ComponentError::ResourceLimitExceeded {
+                name: export_name(namespace, function_name),
+                source,
+            });
+        }
+
+        let error = match error.downcast_ref::<Trap>()? {
+            Trap::Interrupt => ComponentError::ExecutionTimeout {
+                name: export_name(namespace, function_name),
+                elapsed: started.elapsed(),
+            },
+            Trap::OutOfFuel => ComponentError::FuelExhausted {
+                name: export_name(namespace, function_name),
+                budget: self.pre.fuel_limit(self.runtime).unwrap_or(self.runtime.fuel_limit),
+            },
+            _ => return None,
+        };
+        Some(error)
+    }
+}
+
+/// The state of the store at the start of a call, used to build its `CallReport`.
+struct CallStart {
+    /// The id of the call.
+    call_id: u64,
+
+    /// When the call started.
+    started: Instant,
+
+    /// The fuel left in the store when the call started, if fuel is metered.
+    fuel_before: Option<u64>,
+}
+
+/// Loads a precompiled component, rejecting artifacts that are not components or that were built
+/// for another engine.
+fn load_precompiled(engine: &Engine, artifact: &[u8]) -> Result<WasmComponent> {
+    if engine.detect_precompiled(artifact) != Some(Precompiled::Component) {
+        return Err(ComponentError::PrecompiledArtifactInvalid);
+    }
+    // SAFETY: the caller opted in through the unsafe `ComponentBuilder::precompiled`, promising
+    // that the artifact is trusted. Wasmtime still checks that it matches the engine configuration.
+    unsafe { WasmComponent::deserialize(engine, artifact) }
+        .context(PrecompiledArtifactIncompatibleSnafu)
+}
+
+/// Finds the export matching `name` exactly, or the single versioned export `name@<version>`.
+fn match_export_name(exports: &[(String, ComponentItem)], name: &str) -> Result<String> {
+    if exports.iter().any(|(export, _)| export == name) {
+        return Ok(name.to_string());
+    }
+
+    let candidates: Vec<String> = exports
+        .iter()
+        .filter(|(export, _)| {
+            export.split_once('@').is_some_and(|(unversioned, _)| unversioned == name)
+        })
+        .map(|(export, _)| export.clone())
+        .collect();
+    match <[String; 1]>::try_from(candidates) {
+        Ok([export]) => Ok(export),
+        Err(candidates) if candidates.is_empty() => {
+            Err(ComponentError::InterfaceExportNotFound { name: name.to_string() })
+        }
+        Err(candidates) => {
+            Err(ComponentError::AmbiguousExport { name: name.to_string(), candidates })
+        }
+    }
+}
+
+/// Returns a human readable name for the kind of a component item.
+pub(crate) fn item_kind(item: &ComponentItem) -> &'static str {
+    match item {
+        ComponentItem::ComponentFunc(_) => "function",
+        ComponentItem::CoreFunc(_) => "core function",
+        ComponentItem::Module(_) => "module",
+        ComponentItem::Component(_) => "component",
+        ComponentItem::ComponentInstance(_) => "instance",
+        ComponentItem::Type(_) => "type",
+        ComponentItem::Resource(_) => "resource",
+    }
+}
+
+/// Formats the backtrace attached to a failed call, if any, for its error message.
+fn backtrace_suffix(backtrace: &Option<String>) -> String {
+    match backtrace {
+        Some(backtrace) => format!("\n{}", backtrace),
+        None => String::new(),
+    }
+}
+
+/// Formats the path of the coredump written for a failed call, if any, for its error message.
+fn coredump_suffix(coredump: &Option<PathBuf>) -> String {
+    match coredump {
+        Some(path) => format!("\ncoredump written to {}", path.display()),
+        None => String::new(),
+    }
+}
+
+/// Formats the standard error attached to a failed call, if any, for its error message.
+fn stderr_suffix(stderr: &Option<String>) -> String {
+    match stderr {
+        Some(stderr) => format!("\nstderr:\n{}", stderr),
+        None => String::new(),
+    }
+}
+
+/// Formats an export as `namespace#function`, the way it is referred to in errors.
+fn export_name(namespace: Option<&str>, function_name: &str) -> String {
+    match namespace {
+        Some(namespace) => format!("{}#{}", namespace, function_name),
+        None => function_name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn calls_report_the_fuel_they_consume() {
+        let runtime = testing::runtime_builder().consume_fuel(true).build().unwrap();
+        let wasm = testing::wasm(testing::ADD);
+        let component = ComponentBuilder::default().wasm(&*wasm).runtime(&runtime).build().unwrap();
+
+        let (results, report) =
+            component.call(None, "add", &[Val::U32(2), Val::U32(3)]).await.unwrap();
+        assert_eq!(results, [Val::U32(5)]);
+        assert!(report.fuel_consumed.is_some_and(|fuel| fuel > 0));
+    }
+
+    #[tokio::test]
+    async fn component_fuel_limit_overrides_the_runtime() {
+        let runtime = testing::runtime_builder().consume_fuel(true).build().unwrap();
+        let wasm = testing::wasm(testing::SPIN);
+        let component = ComponentBuilder::default()
+            .wasm(&*wasm)
+            .runtime(&runtime)
+            .fuel_limit(10_000)
+            .build()
+            .unwrap();
+
+        let result = component.call(None, "spin", &[]).await;
+        assert!(
+            matches!(result, Err(ComponentError::FuelExhausted { budget: 10_000, .. })),
+            "{:?}",
+            result.err()
+        );
+    }
+
+    #[tokio::test]
+    async fn fuel_limit_is_ignored_without_metering() {
+        let runtime = testing::runtime_builder().build().unwrap();
+        let wasm = testing::wasm(testing::ADD);
+        let component = ComponentBuilder::default()
+            .wasm(&*wasm)
+            .runtime(&runtime)
+            .fuel_limit(1)
+            .build()
+            .unwrap();
+
+        let (_, report) = component.call(None, "add", &[Val::U32(1), Val::U32(1)]).await.unwrap();
+        assert_eq!(report.fuel_consumed, None);
     }
 }
@@ -0,0 +1,274 @@
+//! Runtime composition of components.
+//!
+//! A component can import an interface that another component exports, for example a guardrail
+//! component importing `pawn:chat/handler` from the component that calls the model. When the
+//! import is linked to a provider, every call to one of its functions is forwarded to an instance
+//! of the provider, which lives in its own store inside the state of the importing component.
+//! The store is only created when the import is first called. The provider runs under the
+//! admission permit of the importing instance, so a forwarded call never waits for a slot while
+//! the importer holds one, and each forwarded call runs with the execution limits of the runtime.
+
+use opentelemetry::Context;
+use wasmtime::{
+    component::{
+        types::{self, ComponentItem},
+        ComponentExportIndex, Instance, Linker, Val,
+    },
+    Store,
+};
+
+use crate::{
+    component::{ComponentError, ComponentPre},
+    runtime::StoreSettings,
+    state::State,
+};
+
+type Result<T, E = ComponentError> = core::result::Result<T, E>;
+
+/// An instance of a component providing an import of another component.
+///
+/// The store of the provider is created and the provider instantiated in it on the first call
+/// forwarded to it, and the same instance serves the following calls of the importing component.
+pub(crate) struct LinkedComponent {
+    /// The provider, ready to be instantiated.
+    pre: ComponentPre,
+
+    /// The engine and execution limits the store of the provider is created with.
+    settings: StoreSettings,
+
+    /// The store of the provider and its instance, once instantiated.
+    instance: Option<(Store<State>, Instance)>,
+
+    /// The fuel each forwarded call starts with, or `None` if the runtime does not consume fuel.
+    fuel_limit: Option<u64>,
+}
+
+impl LinkedComponent {
+    /// Creates a provider that is instantiated in a store of its own on its first call.
+    ///
+    /// # Parameters
+    /// - `pre`: The provider, ready to be instantiated.
+    /// - `settings`: The engine and execution limits of the runtime running the importer.
+    pub(crate) fn new(pre: ComponentPre, settings: StoreSettings) -> Self {
+        Self {
+            fuel_limit: pre.fuel_limit_within(settings.fuel_limit()),
+            pre,
+            settings,
+            instance: None,
+        }
+    }
+
+    /// Calls the function exported by the provider at `index`, in the trace context `cx` of the
+    /// importing call.
+    async fn call(
+        &mut self,
+        index: ComponentExportIndex,
+        params: &[Val],
+        results: &mut [Val],
+        cx: Context,
+    ) -> wasmtime::Result<()> {
+        let (store, instance) = match self.instance {
+            Some(ref mut linked) => linked,
+            ref mut slot @ None => {
+                let mut store = self.pre.new_store_with(&self.settings)?;
+                let instance = self.pre.instance_pre().instantiate_async(&mut store).await?;
+                slot.insert((store, instance))
+            }
+        };
+        let instance = *instance;
+
+        store.set_epoch_deadline(self.settings.epoch_deadline_ticks());
+        if let Some(fuel) = self.fuel_limit {
+            // Only fails when fuel is disabled in the engine config
+            let _ = store.set_fuel(fuel);
+        }
+        store.data_mut().set_trace_context(cx);
+        store.data_mut().begin_call();
+        let result = call_instance(store, instance, index, params, results, self.pre.name()).await;
+        store.data().end_call();
+        result
+    }
+}
+
+/// Calls the function at `index` of the provider `instance`, named `name`, living in `store`.
+async fn call_instance(
+    store: &mut Store<State>,
+    instance: Instance,
+    index: ComponentExportIndex,
+    params: &[Val],
+    results: &mut [Val],
+    name: &str,
+) -> wasmtime::Result<()> {
+    let func = instance
+        .get_func(&mut *store, index)
+        .ok_or_else(|| wasmtime::Error::msg(format!("function not exported by {}", name)))?;
+    func.call_async(&mut *store, params, results).await?;
+    func.post_return_async(&mut *store).await
+}
+
+/// Defines the functions of the instance imported by `component` as `import` so that they call
+/// the matching exports of `provider`.
+///
+/// # Parameters
+/// - `linker`: The linker of the importing component.
+/// - `component`: The importing component.
+/// - `import`: The exact name of the imported instance, such as `pawn:chat/handler@0.1.0`.
+/// - `provider`: The component exporting the instance.
+///
+/// # Errors
+///
+/// - `ComponentError::ImportNotFound`: If the component does not import an instance named
+///   `import`.
+/// - `ComponentError::UnsupportedImport`: If the instance holds items other than functions and
+///   types, such as resources, which cannot be forwarded between stores.
+/// - Any error returned by [`ComponentPre::resolve_function_export`] for the provider.
+/// - `ComponentError::ComponentLinkingFailed`: If a function of the provider does not have the
+///   type the component imports, or if the linker already defines the import.
+pub(crate) fn link_import(
+    linker: &mut Linker<State>,
+    component: &wasmtime::component::Component,
+    import: &str,
+    provider: &ComponentPre,
+) -> Result<()> {
+    let engine = component.engine();
+    let instance_type = component
+        .component_type()
+        .imports(engine)
+        .find_map(|(name, item)| match item {
+            ComponentItem::ComponentInstance(instance) if name == import => Some(instance),
+            _ => None,
+        })
+        .ok_or_else(|| ComponentError::ImportNotFound { name: import.to_string() })?;
+
+    let mut instance = linker
+        .instance(import)
+        .map_err(|source| ComponentError::ComponentLinkingFailed { source })?;
+    for (function_name, item) in instance_type.exports(engine) {
+        let expected = match item {
+            ComponentItem::ComponentFunc(func) => func,
+            ComponentItem::Type(_) => continue,
+            item => {
+                return Err(ComponentError::UnsupportedImport {
+                    name: format!("{}#{}", import, function_name),
+                    kind: crate::component::item_kind(&item).to_string(),
+                });
+            }
+        };
+
+        let (index, provided) = provider.resolve_function_export(Some(import), function_name)?;
+        if !signatures_match(&expected, &provided) {
+            return Err(ComponentError::ComponentLinkingFailed {
+                source: wasmtime::Error::msg(format!(
+                    "function {}#{} exported by {} does not have the imported type",
+                    import,
+                    function_name,
+                    provider.name()
+                )),
+            });
+        }
+        let import = import.to_string();
+        instance
+            .func_new_async(function_name, move |mut store, params, results| {
+                let import = import.clone();
+                Box::new(async move {
+                    let cx = store.data().trace_context().clone();
+                    let linked = store.data_mut().linked_mut(&import).ok_or_else(|| {
+                        wasmtime::Error::msg(format!("no provider linked for {}", import))
+                    })?;
+                    linked.call(index, params, results, cx).await
+                })
+            })
+            .map_err(|source| ComponentError::ComponentLinkingFailed { source })?;
+    }
+    Ok(())
+}
+
+/// Returns whether a function of type `provided` can be called as a function of type `expected`,
+/// that is whether their parameters and results have the same types.
+fn signatures_match(expected: &types::ComponentFunc, provided: &types::ComponentFunc) -> bool {
+    expected.params().map(|(_, ty)| ty).eq(provided.params().map(|(_, ty)| ty))
+        && expected.results().eq(provided.results())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{testing, ComponentBuilder, ComponentRegistry};
+
+    /// A component exporting `add` in the interface `pawn:test/math`.
+    const PROVIDER: &str = r#"
+        (component
+          (core module $m
+            (func (export "add") (param i32 i32) (result i32)
+              local.get 0
+              local.get 1
+              i32.add))
+          (core instance $i (instantiate $m))
+          (func $add (param "a" u32) (param "b" u32) (result u32)
+            (canon lift (core func $i "add")))
+          (instance $math (export "add" (func $add)))
+          (export "pawn:test/math@0.1.0" (instance $math)))
+    "#;
+
+    /// A component exporting `double`, which doubles a number with the `add` it imports.
+    const IMPORTER: &str = r#"
+        (component
+          (import "pawn:test/math@0.1.0" (instance $math
+            (export "add" (func (param "a" u32) (param "b" u32) (result u32)))))
+          (core func $add (canon lower (func $math "add")))
+          (core module $m
+            (import "math" "add" (func $add (param i32 i32) (result i32)))
+            (func (export "double") (param i32) (result i32)
+              local.get 0
+              local.get 0
+              call $add))
+          (core instance $i (instantiate $m
+            (with "math" (instance (export "add" (func $add))))))
+          (func (export "double") (param "n" u32) (result u32)
+            (canon lift (core func $i "double"))))
+    "#;
+
+    #[tokio::test]
+    async fn linked_provider_runs_under_the_importer_permit() {
+        // A single slot, which the provider would wait for forever if it needed its own
+        let runtime = testing::runtime_builder().max_concurrent_instances(1usize).build().unwrap();
+        let registry = ComponentRegistry::new(Arc::new(runtime));
+        let provider_wasm = testing::wasm(PROVIDER);
+        let provider =
+            registry.register("math", ComponentBuilder::default().wasm(&*provider_wasm)).unwrap();
+        let importer_wasm = testing::wasm(IMPORTER);
+        registry
+            .register(
+                "double",
+                ComponentBuilder::default()
+                    .wasm(&*importer_wasm)
+                    .link_import("pawn:test/math", &provider),
+            )
+            .unwrap();
+
+        let (results, _) = registry.call("double", None, "double", &[Val::U32(21)]).await.unwrap();
+        assert_eq!(results, [Val::U32(42)]);
+        let stats = registry.runtime().admission.stats();
+        assert_eq!((stats.admitted, stats.in_flight), (1, 0));
+    }
+
+    #[test]
+    fn linking_checks_the_provided_signatures() {
+        let runtime = testing::runtime_builder().build().unwrap();
+        let registry = ComponentRegistry::new(Arc::new(runtime));
+        let provider_wasm = testing::wasm(testing::ADD);
+        let provider =
+            registry.register("add", ComponentBuilder::default().wasm(&*provider_wasm)).unwrap();
+        let importer_wasm = testing::wasm(IMPORTER);
+
+        let result = registry.register(
+            "double",
+            ComponentBuilder::default()
+                .wasm(&*importer_wasm)
+                .link_import("pawn:test/math", &provider),
+        );
+        assert!(matches!(result, Err(ComponentError::InterfaceExportNotFound { .. })));
+    }
+}
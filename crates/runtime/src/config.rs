@@ -0,0 +1,751 @@
+//! Declarative runtime configuration.
+//!
+//! A [`RuntimeConfig`] holds the settings of a [`Runtime`] in a form that can be loaded from a
+//! TOML file, so deployments can be tuned without recompiling. Every section and key is optional
+//! and defaults to the value `RuntimeBuilder` uses:
+//!
+//! ```toml
+//! [engine]
+//! consume_fuel = true
+//! fuel_limit = 1_000_000_000
+//!
+//! [pooling]
+//! max_components = 1000
+//! max_linear_memory = 134_217_728
+//!
+//! [timeouts]
+//! execution_ms = 5000
+//!
+//! [cache]
+//! dir = "/var/cache/pawn"
+//!
+//! [integrity]
+//! require = true
+//! trusted_keys = ["d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"]
+//!
+//! [capabilities.default]
+//! env = { LOG_LEVEL = "info" }
+//!
+//! [[capabilities.chat.http]]
+//! authority = "api.openai.com"
+//! schemes = ["https"]
+//! methods = ["POST"]
+//! ```
+//!
+//! Unknown keys are rejected, and values that parse but make no sense, such as a zero tick
+//! interval, are reported by [`RuntimeConfig::validate`] with the dotted path of their key.
+//!
+//! [`Runtime`]: crate::Runtime
+
+use core::time::Duration;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use http::{uri::Authority, Method};
+use serde::Deserialize;
+use snafu::{ResultExt, Snafu};
+
+use crate::{
+    admission::ADMISSION_TIMEOUT,
+    capability::{CapabilityPolicy, DirAccess, OutputPolicy, Preopen},
+    http_policy::{HttpPolicy, HttpRule},
+    integrity::TrustedKey,
+    runtime::{EPOCH_TICK_INTERVAL, MAX_COMPONENTS, MAX_COMPONENT_SIZE, MAX_LINEAR_MEMORY},
+};
+
+/// The name of the capability policy applied to components without a policy of their own.
+pub const DEFAULT_CAPABILITIES: &str = "default";
+
+#[derive(Debug, Snafu)]
+pub enum ConfigError {
+    #[snafu(display("Failed to read config file {}: {}", path.display(), source))]
+    ReadFailed { path: PathBuf, source: std::io::Error },
+
+    #[snafu(display("Failed to parse config: {}", source))]
+    ParseFailed { source: toml::de::Error },
+
+    #[snafu(display("Invalid value for '{}': {}", key, reason))]
+    InvalidValue { key: String, reason: String },
+}
+
+type Result<T, E = ConfigError> = core::result::Result<T, E>;
+
+/// The settings of a runtime, as loaded from a TOML file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuntimeConfig {
+    /// Settings of the Wasmtime engine.
+    pub engine: EngineConfig,
+
+    /// Limits of the pooling allocator.
+    pub pooling: PoolingConfig,
+
+    /// Execution and queueing timeouts.
+    pub timeouts: TimeoutConfig,
+
+    /// Caps on the instances in flight.
+    pub admission: AdmissionConfig,
+
+    /// Directories of the compiled-component cache and of coredumps.
+    pub cache: CacheConfig,
+
+    /// Verification of component binaries.
+    pub integrity: IntegrityConfig,
+
+    /// Capability policies keyed by component name. The [`DEFAULT_CAPABILITIES`] policy applies
+    /// to components without a policy of their own.
+    pub capabilities: BTreeMap<String, CapabilityConfig>,
+}
+
+/// The `[engine]` section of a runtime config.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    /// Whether to meter guest execution with fuel.
+    pub consume_fuel: bool,
+
+    /// The fuel budget of each store when `consume_fuel` is enabled.
+    pub fuel_limit: u64,
+
+    /// The interval at which the engine epoch is advanced, in milliseconds.
+    pub epoch_tick_interval_ms: u64,
+
+    /// Whether to fail instead of falling back to the on-demand allocator when the pooling
+    /// allocator cannot be created.
+    pub force_pooling_allocator: bool,
+}
+
+/// The `[pooling]` section of a runtime config.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolingConfig {
+    /// The maximum number of component instances.
+    pub max_components: u32,
+
+    /// The maximum size for a component instance, in bytes.
+    pub max_component_size: usize,
+
+    /// The maximum size for a linear memory, in bytes.
+    pub max_linear_memory: usize,
+
+    /// The number of memories each core instance can have.
+    pub memories_per_component: u32,
+
+    /// The number of tables each component instance can have.
+    pub tables_per_component: u32,
+
+    /// The maximum number of core instances each component can have.
+    pub max_core_instances_per_component: u32,
+
+    /// The number of elements in each table.
+    pub table_elements: usize,
+
+    /// The maximum number of tables each component can have.
+    pub max_tables_per_component: u32,
+}
+
+/// The `[timeouts]` section of a runtime config.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutConfig {
+    /// The maximum execution time of a call, in milliseconds.
+    pub execution_ms: u64,
+
+    /// The maximum time a call waits for a free instance slot, in milliseconds.
+    pub admission_ms: u64,
+}
+
+/// The `[admission]` section of a runtime config.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdmissionConfig {
+    /// The maximum number of instances in flight across all components. Defaults to
+    /// `pooling.max_components`.
+    pub max_concurrent_instances: Option<usize>,
+
+    /// The maximum number of instances in flight for a single component. Defaults to
+    /// `max_concurrent_instances`.
+    pub max_instances_per_component: Option<usize>,
+}
+
+/// The `[cache]` section of a runtime config.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// The directory of the persistent compiled-component cache.
+    pub dir: Option<PathBuf>,
+
+    /// The directory coredumps of trapped calls are written to.
+    pub coredump_dir: Option<PathBuf>,
+}
+
+/// The `[integrity]` section of a runtime config.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IntegrityConfig {
+    /// Whether components must carry a pinned digest or a signature.
+    pub require: bool,
+
+    /// The ed25519 public keys whose signatures are accepted, as hex strings.
+    pub trusted_keys: Vec<String>,
+}
+
+/// A `[capabilities.<component>]` section of a runtime config, describing a `CapabilityPolicy`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CapabilityConfig {
+    /// Environment variables visible to the component.
+    pub env: BTreeMap<String, String>,
+
+    /// Command-line arguments visible to the component.
+    pub args: Vec<String>,
+
+    /// Host directories the component may access.
+    pub preopens: Vec<PreopenConfig>,
+
+    /// Whether the component reads the host's standard input.
+    pub inherit_stdin: bool,
+
+    /// Where the component's standard output goes.
+    pub stdout: OutputPolicy,
+
+    /// Where the component's standard error goes.
+    pub stderr: OutputPolicy,
+
+    /// Whether the component may open TCP and UDP sockets.
+    pub allow_sockets: bool,
+
+    /// Whether the component may resolve host names.
+    pub allow_ip_name_lookup: bool,
+
+    /// The rules allowing outgoing HTTP requests.
+    pub http: Vec<HttpRuleConfig>,
+}
+
+/// A host directory made available to a component, in a `CapabilityConfig`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PreopenConfig {
+    /// The directory on the host.
+    pub host_path: PathBuf,
+
+    /// The path under which the component sees the directory.
+    pub guest_path: String,
+
+    /// What the component may do with the directory and its files.
+    #[serde(default)]
+    pub access: DirAccess,
+}
+
+/// A rule allowing outgoing HTTP requests, in a `CapabilityConfig`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpRuleConfig {
+    /// The host the rule applies to, with an optional port.
+    pub authority: String,
+
+    /// The allowed schemes. Empty allows any scheme.
+    #[serde(default)]
+    pub schemes: Vec<String>,
+
+    /// The allowed methods, such as `POST`. Empty allows any method.
+    #[serde(default)]
+    pub methods: Vec<String>,
+
+    /// The allowed path prefixes. Empty allows any path.
+    #[serde(default)]
+    pub path_prefixes: Vec<String>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            consume_fuel: false,
+            fuel_limit: u64::MAX,
+            epoch_tick_interval_ms: millis(EPOCH_TICK_INTERVAL),
+            force_pooling_allocator: false,
+        }
+    }
+}
+
+impl Default for PoolingConfig {
+    fn default() -> Self {
+        Self {
+            max_components: MAX_COMPONENTS,
+            max_component_size: MAX_COMPONENT_SIZE as usize,
+            max_linear_memory: MAX_LINEAR_MEMORY as usize,
+            memories_per_component: 1,
+            tables_per_component: 1,
+            max_core_instances_per_component: 30,
+            table_elements: 15_000,
+            max_tables_per_component: 20,
+        }
+    }
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self { execution_ms: 10_000, admission_ms: millis(ADMISSION_TIMEOUT) }
+    }
+}
+
+impl RuntimeConfig {
+    /// Parses and validates a config from TOML.
+    ///
+    /// # Parameters
+    /// - `toml`: The contents of the config file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the config.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::ParseFailed` if the TOML is malformed, holds unknown keys or values
+    /// of the wrong type, and `ConfigError::InvalidValue` if a value fails validation.
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        let config: Self = toml::from_str(toml).context(ParseFailedSnafu)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reads, parses and validates a config file.
+    ///
+    /// # Parameters
+    /// - `path`: The path of the TOML config file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the config.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::ReadFailed` if the file cannot be read, and the errors of
+    /// [`RuntimeConfig::from_toml`] otherwise.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let toml = fs::read_to_string(path).context(ReadFailedSnafu { path })?;
+        Self::from_toml(&toml)
+    }
+
+    /// Checks that every value of the config is usable.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidValue` naming the key of the first invalid value, such as
+    /// `pooling.max_components` or `capabilities.chat.http[0].methods[1]`.
+    pub fn validate(&self) -> Result<()> {
+        let engine = &self.engine;
+        positive("engine.epoch_tick_interval_ms", engine.epoch_tick_interval_ms)?;
+        if engine.consume_fuel {
+            positive("engine.fuel_limit", engine.fuel_limit)?;
+        }
+
+        let pooling = &self.pooling;
+        positive("pooling.max_components", pooling.max_components)?;
+        positive("pooling.max_component_size", pooling.max_component_size)?;
+        positive("pooling.max_linear_memory", pooling.max_linear_memory)?;
+        positive("pooling.memories_per_component", pooling.memories_per_component)?;
+        positive("pooling.tables_per_component", pooling.tables_per_component)?;
+        positive(
+            "pooling.max_core_instances_per_component",
+            pooling.max_core_instances_per_component,
+        )?;
+        positive("pooling.max_tables_per_component", pooling.max_tables_per_component)?;
+        // The pooling allocator sizes its pools with these products, which must fit in a u32
+        for (key, per_component) in [
+            ("pooling.memories_per_component", pooling.memories_per_component),
+            ("pooling.tables_per_component", pooling.tables_per_component),
+        ] {
+            if pooling.max_components.checked_mul(per_component).is_none() {
+                return invalid(key, "multiplied by pooling.max_components, overflows a u32");
+            }
+        }
+
+        positive("timeouts.execution_ms", self.timeouts.execution_ms)?;
+        positive("timeouts.admission_ms", self.timeouts.admission_ms)?;
+
+        let max_concurrent_instances = self.max_concurrent_instances();
+        if let Some(max) = self.admission.max_concurrent_instances {
+            positive("admission.max_concurrent_instances", max)?;
+        }
+        if let Some(max) = self.admission.max_instances_per_component {
+            positive("admission.max_instances_per_component", max)?;
+            if max > max_concurrent_instances {
+                return invalid(
+                    "admission.max_instances_per_component",
+                    format!(
+                        "{} exceeds the {} instances allowed across all components",
+                        max, max_concurrent_instances
+                    ),
+                );
+            }
+        }
+
+        for (key, dir) in
+            [("cache.dir", &self.cache.dir), ("cache.coredump_dir", &self.cache.coredump_dir)]
+        {
+            if dir.as_ref().is_some_and(|dir| dir.as_os_str().is_empty()) {
+                return invalid(key, "must not be empty");
+            }
+        }
+
+        self.trusted_keys()?;
+
+        for (name, capabilities) in &self.capabilities {
+            capabilities.to_policy(&format!("capabilities.{}", name))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the capability policy of a component.
+    ///
+    /// # Parameters
+    /// - `component`: The name of the component.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the policy configured for `component`, the [`DEFAULT_CAPABILITIES`]
+    /// policy if it has none, or a policy granting nothing if neither is configured.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidValue` if the policy does not validate.
+    pub fn capability_policy(&self, component: &str) -> Result<CapabilityPolicy> {
+        let name = match self.capabilities.contains_key(component) {
+            true => component,
+            false => DEFAULT_CAPABILITIES,
+        };
+        match self.capabilities.get(name) {
+            Some(capabilities) => capabilities.to_policy(&format!("capabilities.{}", name)),
+            None => Ok(CapabilityPolicy::default()),
+        }
+    }
+
+    /// Returns the keys whose signatures are accepted on components.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidValue` if a key is not a hex-encoded ed25519 public key.
+    pub fn trusted_keys(&self) -> Result<Vec<TrustedKey>> {
+        self.integrity
+            .trusted_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                TrustedKey::from_hex(key)
+                    .or_else(|e| invalid(format!("integrity.trusted_keys[{}]", i), e.to_string()))
+            })
+            .collect()
+    }
+
+    /// Returns the maximum execution time of a call.
+    pub fn max_execution_time(&self) -> Duration {
+        Duration::from_millis(self.timeouts.execution_ms)
+    }
+
+    /// Returns the maximum time a call waits for a free instance slot.
+    pub fn admission_timeout(&self) -> Duration {
+        Duration::from_millis(self.timeouts.admission_ms)
+    }
+
+    /// Returns the interval at which the engine epoch is advanced.
+    pub fn epoch_tick_interval(&self) -> Duration {
+        Duration::from_millis(self.engine.epoch_tick_interval_ms)
+    }
+
+    /// Returns the maximum number of instances in flight across all components.
+    pub fn max_concurrent_instances(&self) -> usize {
+        self.admission.max_concurrent_instances.unwrap_or(self.pooling.max_components as usize)
+    }
+
+    /// Returns the maximum number of instances in flight for a single component.
+    pub fn max_instances_per_component(&self) -> usize {
+        self.admission
+            .max_instances_per_component
+            .unwrap_or_else(|| self.max_concurrent_instances())
+    }
+}
+
+impl CapabilityConfig {
+    /// Converts the section at `key` to a `CapabilityPolicy`, validating its values.
+    fn to_policy(&self, key: &str) -> Result<CapabilityPolicy> {
+        for name in self.env.keys() {
+            if name.is_empty() || name.contains(['=', '\0']) {
+                return invalid(
+                    format!("{}.env.{}", key, name),
+                    "names must be non-empty and contain neither '=' nor NUL",
+                );
+            }
+        }
+
+        let preopens = self
+            .preopens
+            .iter()
+            .enumerate()
+            .map(|(i, preopen)| {
+                let key = format!("{}.preopens[{}]", key, i);
+                if preopen.host_path.as_os_str().is_empty() {
+                    return invalid(format!("{}.host_path", key), "must not be empty");
+                }
+                if preopen.guest_path.is_empty() {
+                    return invalid(format!("{}.guest_path", key), "must not be empty");
+                }
+                Ok(Preopen {
+                    host_path: preopen.host_path.clone(),
+                    guest_path: preopen.guest_path.clone(),
+                    access: preopen.access,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let rules = self
+            .http
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| rule.to_rule(&format!("{}.http[{}]", key, i)))
+            .collect::<Result<_>>()?;
+
+        Ok(CapabilityPolicy {
+            env: self.env.iter().map(|(name, value)| (name.clone(), value.clone())).collect(),
+            args: self.args.clone(),
+            preopens,
+            inherit_stdin: self.inherit_stdin,
+            stdout: self.stdout,
+            stderr: self.stderr,
+            allow_sockets: self.allow_sockets,
+            allow_ip_name_lookup: self.allow_ip_name_lookup,
+            http: HttpPolicy { rules },
+        })
+    }
+}
+
+impl HttpRuleConfig {
+    /// Converts the rule at `key` to an `HttpRule`, validating its values.
+    fn to_rule(&self, key: &str) -> Result<HttpRule> {
+        if let Err(e) = self.authority.parse::<Authority>() {
+            return invalid(format!("{}.authority", key), e.to_string());
+        }
+        for (i, scheme) in self.schemes.iter().enumerate() {
+            if scheme.is_empty()
+                || !scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+            {
+                return invalid(format!("{}.schemes[{}]", key, i), "not a valid URI scheme");
+            }
+        }
+        let methods = self
+            .methods
+            .iter()
+            .enumerate()
+            .map(|(i, method)| {
+                Method::from_bytes(method.as_bytes())
+                    .or_else(|e| invalid(format!("{}.methods[{}]", key, i), e.to_string()))
+            })
+            .collect::<Result<_>>()?;
+        for (i, prefix) in self.path_prefixes.iter().enumerate() {
+            if !prefix.starts_with('/') {
+                return invalid(format!("{}.path_prefixes[{}]", key, i), "must start with '/'");
+            }
+        }
+        Ok(HttpRule {
+            authority: self.authority.clone(),
+            schemes: self.schemes.clone(),
+            methods,
+            path_prefixes: self.path_prefixes.clone(),
+        })
+    }
+}
+
+/// Returns the error of an invalid value at `key`.
+fn invalid<T>(key: impl Into<String>, reason: impl Into<String>) -> Result<T> {
+    Err(ConfigError::InvalidValue { key: key.into(), reason: reason.into() })
+}
+
+/// Rejects a zero value at `key`.
+fn positive<T: PartialEq + Default>(key: &str, value: T) -> Result<()> {
+    match value == T::default() {
+        true => invalid(key, "must be greater than zero"),
+        false => Ok(()),
+    }
+}
+
+/// Returns a duration in whole milliseconds.
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the key of the `InvalidValue` error of `toml`.
+    fn invalid_key(toml: &str) -> String {
+        match RuntimeConfig::from_toml(toml) {
+            Err(ConfigError::InvalidValue { key, .. }) => key,
+            result => panic!("expected an invalid value, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn empty_config_uses_the_defaults() {
+        let config = RuntimeConfig::from_toml("").unwrap();
+        assert_eq!(config, RuntimeConfig::default());
+        assert_eq!(config.max_concurrent_instances(), MAX_COMPONENTS as usize);
+        assert_eq!(config.max_instances_per_component(), MAX_COMPONENTS as usize);
+        assert_eq!(config.admission_timeout(), ADMISSION_TIMEOUT);
+        assert_eq!(config.capability_policy("chat").unwrap(), CapabilityPolicy::default());
+    }
+
+    #[test]
+    fn sections_are_parsed() {
+        let config = RuntimeConfig::from_toml(
+            r#"
+            [engine]
+            consume_fuel = true
+            fuel_limit = 1_000
+
+            [pooling]
+            max_components = 8
+
+            [timeouts]
+            execution_ms = 250
+
+            [admission]
+            max_instances_per_component = 2
+
+            [cache]
+            dir = "/var/cache/pawn"
+
+            [capabilities.default]
+            env = { LOG_LEVEL = "info" }
+            stdout = "inherit"
+
+            [[capabilities.chat.http]]
+            authority = "api.openai.com"
+            schemes = ["https"]
+            methods = ["POST"]
+            path_prefixes = ["/v1/"]
+
+            [[capabilities.chat.preopens]]
+            host_path = "/srv/data"
+            guest_path = "/data"
+            access = "read-write"
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.engine.consume_fuel);
+        assert_eq!(config.engine.fuel_limit, 1_000);
+        assert_eq!(config.pooling.max_components, 8);
+        assert_eq!(config.max_execution_time(), Duration::from_millis(250));
+        assert_eq!(config.max_concurrent_instances(), 8);
+        assert_eq!(config.max_instances_per_component(), 2);
+        assert_eq!(config.cache.dir, Some(PathBuf::from("/var/cache/pawn")));
+
+        let default = config.capability_policy("other").unwrap();
+        assert_eq!(default.env, vec![("LOG_LEVEL".to_string(), "info".to_string())]);
+        assert_eq!(default.stdout, OutputPolicy::Inherit);
+
+        let chat = config.capability_policy("chat").unwrap();
+        assert!(chat.env.is_empty());
+        assert_eq!(chat.http.rules, vec![HttpRule {
+            authority: "api.openai.com".to_string(),
+            schemes: vec!["https".to_string()],
+            methods: vec![Method::POST],
+            path_prefixes: vec!["/v1/".to_string()],
+        }]);
+        assert_eq!(chat.preopens, vec![Preopen {
+            host_path: PathBuf::from("/srv/data"),
+            guest_path: "/data".to_string(),
+            access: DirAccess::ReadWrite,
+        }]);
+    }
+
+    #[test]
+    fn unknown_keys_and_wrong_types_fail_to_parse() {
+        for toml in ["[engine]\nfuel = 1", "[pooling]\nmax_components = \"many\"", "[extra]"] {
+            assert!(
+                matches!(RuntimeConfig::from_toml(toml), Err(ConfigError::ParseFailed { .. })),
+                "{}",
+                toml
+            );
+        }
+    }
+
+    #[test]
+    fn zero_limits_are_invalid() {
+        assert_eq!(invalid_key("[pooling]\nmax_components = 0"), "pooling.max_components");
+        assert_eq!(invalid_key("[timeouts]\nadmission_ms = 0"), "timeouts.admission_ms");
+        assert_eq!(
+            invalid_key("[engine]\nconsume_fuel = true\nfuel_limit = 0"),
+            "engine.fuel_limit"
+        );
+        // The fuel limit only matters when fuel is consumed
+        assert!(RuntimeConfig::from_toml("[engine]\nfuel_limit = 0").is_ok());
+    }
+
+    #[test]
+    fn pool_sizes_must_fit_in_a_u32() {
+        assert_eq!(
+            invalid_key("[pooling]\nmax_components = 4294967295\nmemories_per_component = 2"),
+            "pooling.memories_per_component"
+        );
+    }
+
+    #[test]
+    fn admission_caps_must_be_consistent() {
+        assert_eq!(
+            invalid_key(
+                "[admission]\nmax_concurrent_instances = 4\nmax_instances_per_component = 5"
+            ),
+            "admission.max_instances_per_component"
+        );
+        assert_eq!(
+            invalid_key("[admission]\nmax_concurrent_instances = 0"),
+            "admission.max_concurrent_instances"
+        );
+    }
+
+    #[test]
+    fn invalid_values_are_located_by_dotted_keys() {
+        assert_eq!(invalid_key("[cache]\ncoredump_dir = \"\""), "cache.coredump_dir");
+        assert_eq!(
+            invalid_key("[integrity]\ntrusted_keys = [\"00\"]"),
+            "integrity.trusted_keys[0]"
+        );
+        assert_eq!(
+            invalid_key("[capabilities.chat]\nenv = { \"A=B\" = \"1\" }"),
+            "capabilities.chat.env.A=B"
+        );
+        assert_eq!(
+            invalid_key("[[capabilities.chat.preopens]]\nhost_path = \"/srv\"\nguest_path = \"\""),
+            "capabilities.chat.preopens[0].guest_path"
+        );
+        assert_eq!(
+            invalid_key("[[capabilities.chat.http]]\nauthority = \"a b\""),
+            "capabilities.chat.http[0].authority"
+        );
+        assert_eq!(
+            invalid_key(
+                "[[capabilities.chat.http]]\nauthority = \"example.com\"\n\
+                 methods = [\"GET\", \"BAD METHOD\"]"
+            ),
+            "capabilities.chat.http[0].methods[1]"
+        );
+        assert_eq!(
+            invalid_key(
+                "[[capabilities.chat.http]]\nauthority = \"example.com\"\nschemes = [\"ht tp\"]"
+            ),
+            "capabilities.chat.http[0].schemes[0]"
+        );
+        assert_eq!(
+            invalid_key(
+                "[[capabilities.chat.http]]\nauthority = \"example.com\"\npath_prefixes = [\"v1\"]"
+            ),
+            "capabilities.chat.http[0].path_prefixes[0]"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Wasm coredumps of trapped calls.
+//!
+//! When `Runtime::coredump_dir` is set, the engine captures a coredump whenever guest code traps.
+//! The dump is written in the standard [Wasm coredump format] so it can be inspected offline with
+//! tools such as `wasmgdb`, and its path is attached to the error of the call.
+//!
+//! [Wasm coredump format]: https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use wasmtime::{Store, WasmCoreDump};
+
+use crate::{cache::temp_path, state::State};
+
+/// The extension of coredump files.
+pub const COREDUMP_EXTENSION: &str = "coredump";
+
+/// Returns the path of the coredump of a call, `<dir>/<component-hash>-<call-id>.coredump`.
+pub(crate) fn coredump_path(dir: &Path, component_hash: &str, call_id: u64) -> PathBuf {
+    dir.join(format!("{}-{}.{}", component_hash, call_id, COREDUMP_EXTENSION))
+}
+
+/// Serializes the coredump of a trapped call and writes it to `dir`.
+///
+/// The file is written to a temporary path first and then renamed, so readers never observe a
+/// partial dump.
+///
+/// # Parameters
+/// - `dir`: The directory to write the coredump to. It is created if needed.
+/// - `store`: The store the trap happened in.
+/// - `dump`: The coredump captured by the engine.
+/// - `component_hash`: The sha256 of the component binary, as a hex string.
+/// - `call_id`: The id of the call that trapped.
+///
+/// # Returns
+///
+/// A `Result` containing the path of the coredump.
+pub(crate) fn write_coredump(
+    dir: &Path,
+    store: &mut Store<State>,
+    dump: &WasmCoreDump,
+    component_hash: &str,
+    call_id: u64,
+) -> std::io::Result<PathBuf> {
+    let name = store.data().name().to_string();
+    let bytes = dump.serialize(&mut *store, &name);
+    let path = coredump_path(dir, component_hash, call_id);
+    fs::create_dir_all(dir)?;
+    let tmp = temp_path(&path, COREDUMP_EXTENSION);
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, &path)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+    use crate::{cache::hex, testing, Component, ComponentError};
+
+    #[tokio::test]
+    async fn trapped_calls_write_a_coredump() {
+        let dir = testing::TempDir::new("pawn-coredump");
+        let runtime = testing::runtime_builder().coredump_dir(dir.path()).build().unwrap();
+        let wasm = testing::wasm(testing::STATEFUL);
+
+        let result =
+            Component::with_runtime(&wasm, &runtime).unwrap().call(None, "trap", &[]).await;
+        let Err(ComponentError::ComponentFunctionCallFailed { coredump: Some(path), .. }) = result
+        else {
+            panic!("expected a coredump, got {:?}", result.err());
+        };
+        assert_eq!(path.parent(), Some(dir.path()));
+        assert_eq!(path.extension().unwrap(), COREDUMP_EXTENSION);
+        assert!(fs::read(&path).unwrap().starts_with(b"\0asm"));
+        let hash = hex(&Sha256::digest(&wasm));
+        let file_name = path.file_name().unwrap().to_string_lossy();
+        assert!(file_name.starts_with(&format!("{}-", hash)), "{}", file_name);
+    }
+
+    #[tokio::test]
+    async fn coredumps_are_only_written_with_a_directory() {
+        let runtime = testing::runtime_builder().build().unwrap();
+        let wasm = testing::wasm(testing::STATEFUL);
+
+        let result =
+            Component::with_runtime(&wasm, &runtime).unwrap().call(None, "trap", &[]).await;
+        assert!(matches!(
+            result,
+            Err(ComponentError::ComponentFunctionCallFailed { coredump: None, .. })
+        ));
+    }
+}
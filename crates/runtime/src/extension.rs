@@ -0,0 +1,171 @@
+//! Host interfaces provided by embedders.
+//!
+//! Out of the box, components are linked against WASI and `wasi:http` only. A [`HostExtension`]
+//! adds more host interfaces, such as tools, storage or clocks, to the linker of a component,
+//! and can attach its own typed data to the [`State`] of every store the component runs in.
+
+use core::any::{Any, TypeId};
+
+use hashbrown::HashMap;
+use wasmtime::component::Linker;
+
+use crate::state::State;
+
+/// Provides host interfaces to components, in addition to WASI and `wasi:http`.
+///
+/// Extensions are added to a component with [`ComponentBuilder::extension`], and are shared by
+/// all the instances of the component.
+///
+/// [`ComponentBuilder::extension`]: crate::ComponentBuilder::extension
+pub trait HostExtension: Send + Sync + 'static {
+    /// Returns the name of the extension, used in errors.
+    fn name(&self) -> &str;
+
+    /// Returns the interfaces the extension provides, such as `pawn:host/clock@0.1.0`.
+    fn interfaces(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Adds the functions and resources of the extension to the linker of a component.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an item cannot be defined, for example because another extension
+    /// already defined it.
+    fn add_to_linker(&self, linker: &mut Linker<State>) -> wasmtime::Result<()>;
+
+    /// Prepares the state of a new store before the component is instantiated in it, typically
+    /// by attaching the extension's data with [`State::insert_extension`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data of the extension cannot be created.
+    fn init_state(&self, _state: &mut State) -> wasmtime::Result<()> {
+        Ok(())
+    }
+}
+
+/// The data attached to a `State` by host extensions, keyed by type.
+#[derive(Default)]
+pub(crate) struct Extensions {
+    /// The data of each type.
+    data: HashMap<TypeId, Box<dyn Any + Send>>,
+}
+
+impl Extensions {
+    /// Attaches `value`, returning the value of the same type attached before, if any.
+    pub(crate) fn insert<T: Send + 'static>(&mut self, value: T) -> Option<T> {
+        self.data
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    /// Returns the value of type `T`, if one is attached.
+    pub(crate) fn get<T: Send + 'static>(&self) -> Option<&T> {
+        self.data.get(&TypeId::of::<T>()).and_then(|value| value.downcast_ref())
+    }
+
+    /// Returns the value of type `T` mutably, if one is attached.
+    pub(crate) fn get_mut<T: Send + 'static>(&mut self) -> Option<&mut T> {
+        self.data.get_mut(&TypeId::of::<T>()).and_then(|value| value.downcast_mut())
+    }
+
+    /// Detaches and returns the value of type `T`, if one is attached.
+    pub(crate) fn remove<T: Send + 'static>(&mut self) -> Option<T> {
+        self.data
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok())
+            .map(|value| *value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmtime::{component::Val, StoreContextMut};
+
+    use super::*;
+    use crate::{testing, ComponentBuilder, ComponentError};
+
+    /// A component exporting `now`, which returns the time of the `pawn:test/clock` it imports.
+    const CLOCK_USER: &str = r#"
+        (component
+          (import "pawn:test/clock@0.1.0" (instance $clock
+            (export "now" (func (result u64)))))
+          (core func $now (canon lower (func $clock "now")))
+          (core module $m
+            (import "clock" "now" (func $now (result i64)))
+            (func (export "now") (result i64)
+              call $now))
+          (core instance $i (instantiate $m
+            (with "clock" (instance (export "now" (func $now))))))
+          (func (export "now") (result u64) (canon lift (core func $i "now"))))
+    "#;
+
+    /// A clock stopped at the time it holds.
+    struct StoppedClock(u64);
+
+    /// The time of the clock, attached to each store.
+    struct Time(u64);
+
+    impl HostExtension for StoppedClock {
+        fn name(&self) -> &str {
+            "clock"
+        }
+
+        fn interfaces(&self) -> Vec<String> {
+            vec!["pawn:test/clock@0.1.0".to_string()]
+        }
+
+        fn add_to_linker(&self, linker: &mut Linker<State>) -> wasmtime::Result<()> {
+            linker.instance("pawn:test/clock@0.1.0")?.func_wrap(
+                "now",
+                |store: StoreContextMut<'_, State>, (): ()| {
+                    let time = store.data().extension::<Time>().map_or(0, |time| time.0);
+                    Ok((time,))
+                },
+            )
+        }
+
+        fn init_state(&self, state: &mut State) -> wasmtime::Result<()> {
+            state.insert_extension(Time(self.0));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn components_call_the_interfaces_of_extensions() {
+        let runtime = testing::runtime_builder().build().unwrap();
+        let wasm = testing::wasm(CLOCK_USER);
+        let component = ComponentBuilder::default()
+            .wasm(&*wasm)
+            .runtime(&runtime)
+            .extension(StoppedClock(42))
+            .build()
+            .unwrap();
+
+        let (results, _) = component.call(None, "now", &[]).await.unwrap();
+        assert_eq!(results, [Val::U64(42)]);
+    }
+
+    #[test]
+    fn imports_no_extension_provides_are_rejected() {
+        let runtime = testing::runtime_builder().build().unwrap();
+        let wasm = testing::wasm(CLOCK_USER);
+
+        let result = ComponentBuilder::default().wasm(&*wasm).runtime(&runtime).build();
+        assert!(matches!(result, Err(ComponentError::ValidationFailed { .. })));
+    }
+
+    #[test]
+    fn extension_data_is_keyed_by_type() {
+        let mut extensions = Extensions::default();
+        assert_eq!(extensions.insert(Time(1)).map(|time| time.0), None);
+        assert_eq!(extensions.insert(Time(2)).map(|time| time.0), Some(1));
+        extensions.get_mut::<Time>().unwrap().0 += 1;
+        assert_eq!(extensions.get::<Time>().map(|time| time.0), Some(3));
+        assert!(extensions.get::<StoppedClock>().is_none());
+        assert_eq!(extensions.remove::<Time>().map(|time| time.0), Some(3));
+        assert!(extensions.get::<Time>().is_none());
+    }
+}
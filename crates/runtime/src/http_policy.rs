@@ -0,0 +1,202 @@
+//! Outgoing HTTP policy of a component.
+//!
+//! Components reach the network through `wasi:http/outgoing-handler`, which the host serves on
+//! their behalf. An [`HttpPolicy`] lists the requests the host may send; any other request fails
+//! with `ErrorCode::HttpRequestDenied` before a connection is opened.
+
+use http::{uri::Authority, Method, Request, Uri};
+
+/// The outgoing HTTP requests a component may send. Nothing is allowed by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpPolicy {
+    /// The rules allowing requests. A request is allowed if any rule matches it.
+    pub rules: Vec<HttpRule>,
+}
+
+/// Allows requests to a single authority, optionally restricted by scheme, method and path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRule {
+    /// The host the rule applies to, with an optional port, such as `api.openai.com` or
+    /// `localhost:8080`. Without a port, the rule matches the host on any port. Hosts are
+    /// compared case-insensitively.
+    pub authority: String,
+
+    /// The allowed schemes, such as `https`. Empty allows any scheme.
+    pub schemes: Vec<String>,
+
+    /// The allowed methods. Empty allows any method.
+    pub methods: Vec<Method>,
+
+    /// The allowed path prefixes, such as `/v1/`. Empty allows any path. Paths holding `.` or `..`
+    /// segments, even percent-encoded, never match a prefix, as the server may resolve them to a
+    /// path outside of it.
+    pub path_prefixes: Vec<String>,
+}
+
+impl HttpPolicy {
+    /// Creates a policy allowing any request to the given authorities.
+    ///
+    /// # Parameters
+    /// - `authorities`: The hosts the component may call, with optional ports.
+    ///
+    /// # Returns
+    ///
+    /// A new `HttpPolicy` with one unrestricted rule per authority.
+    pub fn allow_authorities<I, S>(authorities: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self { rules: authorities.into_iter().map(HttpRule::new).collect() }
+    }
+
+    /// Returns whether the policy allows sending `request`.
+    pub fn allows<B>(&self, request: &Request<B>) -> bool {
+        self.rules.iter().any(|rule| rule.matches(request))
+    }
+}
+
+impl HttpRule {
+    /// Creates a rule allowing any request to `authority`.
+    ///
+    /// # Parameters
+    /// - `authority`: The host the rule applies to, with an optional port.
+    ///
+    /// # Returns
+    ///
+    /// A new `HttpRule` that does not restrict schemes, methods or paths.
+    pub fn new(authority: impl Into<String>) -> Self {
+        Self {
+            authority: authority.into(),
+            schemes: Vec::new(),
+            methods: Vec::new(),
+            path_prefixes: Vec::new(),
+        }
+    }
+
+    /// Returns whether the rule allows sending `request`.
+    pub fn matches<B>(&self, request: &Request<B>) -> bool {
+        let uri = request.uri();
+        let authority_matches = authority_matches(&self.authority, uri);
+        let scheme_matches = self.schemes.is_empty()
+            || uri.scheme_str().is_some_and(|scheme| {
+                self.schemes.iter().any(|allowed| allowed.eq_ignore_ascii_case(scheme))
+            });
+        let method_matches = self.methods.is_empty() || self.methods.contains(request.method());
+        let path_matches = self.path_prefixes.is_empty()
+            || (!has_dot_segment(uri.path())
+                && self.path_prefixes.iter().any(|prefix| uri.path().starts_with(prefix.as_str())));
+
+        authority_matches && scheme_matches && method_matches && path_matches
+    }
+}
+
+/// Returns whether the authority of `uri` matches `pattern`, a host with an optional port.
+///
+/// Hosts are compared case-insensitively. A pattern without a port matches the host on any port,
+/// and a URI without a port uses the default port of its scheme. A pattern with anything but a
+/// valid port next to its host, such as `example.com:http`, matches nothing.
+pub(crate) fn authority_matches(pattern: &str, uri: &Uri) -> bool {
+    let (Some(authority), Ok(allowed)) = (uri.authority(), pattern.parse::<Authority>()) else {
+        return false;
+    };
+    // `Authority` ignores a port it cannot parse, which would let the pattern match any port
+    if allowed.as_str() != allowed.host() && allowed.port_u16().is_none() {
+        return false;
+    }
+    let port = authority.port_u16().or_else(|| match uri.scheme_str() {
+        Some("http") => Some(80),
+        Some("https") => Some(443),
+        _ => None,
+    });
+    authority.host().eq_ignore_ascii_case(allowed.host())
+        && allowed.port_u16().is_none_or(|allowed| port == Some(allowed))
+}
+
+/// Returns whether `path` holds a `.` or `..` segment, with its dots optionally percent-encoded.
+///
+/// `Uri` does not remove dot segments, so `/v1/../admin` starts with `/v1/` while the server
+/// serves `/admin`.
+fn has_dot_segment(path: &str) -> bool {
+    path.split('/').any(|segment| {
+        let segment = segment.to_ascii_lowercase().replace("%2e", ".");
+        segment == "." || segment == ".."
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a request with no body.
+    fn request(method: Method, uri: &str) -> Request<()> {
+        Request::builder().method(method).uri(uri).body(()).unwrap()
+    }
+
+    #[test]
+    fn empty_policy_denies_everything() {
+        assert!(!HttpPolicy::default().allows(&request(Method::GET, "https://example.com/")));
+    }
+
+    #[test]
+    fn authorities_match_hosts_case_insensitively() {
+        let policy = HttpPolicy::allow_authorities(["API.example.com"]);
+        assert!(policy.allows(&request(Method::POST, "https://api.example.com/v1/chat")));
+        assert!(policy.allows(&request(Method::GET, "http://api.EXAMPLE.com:8080/")));
+        assert!(!policy.allows(&request(Method::GET, "https://example.com/")));
+        assert!(!policy.allows(&request(Method::GET, "https://api.example.com.evil.net/")));
+        assert!(!policy.allows(&request(Method::GET, "https://api.example.com@evil.net/")));
+        assert!(!policy.allows(&request(Method::GET, "/relative")));
+    }
+
+    #[test]
+    fn ports_default_to_the_scheme() {
+        let policy = HttpPolicy::allow_authorities(["example.com:443", "localhost:8080"]);
+        assert!(policy.allows(&request(Method::GET, "https://example.com/")));
+        assert!(policy.allows(&request(Method::GET, "http://example.com:443/")));
+        assert!(!policy.allows(&request(Method::GET, "http://example.com/")));
+        assert!(policy.allows(&request(Method::GET, "http://localhost:8080/")));
+        assert!(!policy.allows(&request(Method::GET, "http://localhost/")));
+    }
+
+    #[test]
+    fn rules_restrict_schemes_methods_and_paths() {
+        let policy = HttpPolicy {
+            rules: vec![HttpRule {
+                authority: "api.example.com".to_string(),
+                schemes: vec!["HTTPS".to_string()],
+                methods: vec![Method::POST],
+                path_prefixes: vec!["/v1/".to_string()],
+            }],
+        };
+        assert!(policy.allows(&request(Method::POST, "https://api.example.com/v1/chat")));
+        assert!(!policy.allows(&request(Method::POST, "http://api.example.com/v1/chat")));
+        assert!(!policy.allows(&request(Method::GET, "https://api.example.com/v1/chat")));
+        assert!(!policy.allows(&request(Method::POST, "https://api.example.com/v2/chat")));
+        assert!(!policy.allows(&request(Method::POST, "https://api.example.com/v1")));
+        assert!(!policy.allows(&request(Method::POST, "https://api.example.com/v1/../admin")));
+        assert!(!policy.allows(&request(Method::POST, "https://api.example.com/v1/%2e%2e/admin")));
+        assert!(!policy.allows(&request(Method::POST, "https://api.example.com/v1/.%2E/admin")));
+        assert!(!policy.allows(&request(Method::POST, "https://api.example.com/v1/./chat")));
+        assert!(policy.allows(&request(Method::POST, "https://api.example.com/v1/..chat")));
+    }
+
+    #[test]
+    fn any_matching_rule_allows_a_request() {
+        let mut upload = HttpRule::new("files.example.com");
+        upload.methods = vec![Method::PUT];
+        let policy = HttpPolicy { rules: vec![HttpRule::new("api.example.com"), upload] };
+        assert!(policy.allows(&request(Method::DELETE, "https://api.example.com/")));
+        assert!(policy.allows(&request(Method::PUT, "https://files.example.com/a")));
+        assert!(!policy.allows(&request(Method::GET, "https://files.example.com/a")));
+    }
+
+    #[test]
+    fn invalid_patterns_match_nothing() {
+        let uri: Uri = "https://example.com/".parse().unwrap();
+        assert!(!authority_matches("", &uri));
+        assert!(!authority_matches("example.com:http", &uri));
+        assert!(!authority_matches("example.com:99999", &uri));
+        assert!(!authority_matches("user@example.com", &uri));
+    }
+}
@@ -0,0 +1,288 @@
+//! Serving HTTP requests with components exporting `wasi:http/incoming-handler`.
+//!
+//! An [`HttpTrigger`] maps path prefixes to components of a [`ComponentRegistry`], and hands each
+//! request to the component of the longest matching prefix. Every request runs in a fresh store,
+//! admitted by the runtime's `AdmissionControl` like any other call, with the capabilities,
+//! extensions and links the component was registered with. Errors are logged and answered with a
+//! bare status code, so nothing about the host leaks to clients.
+
+use core::fmt::Display;
+use std::{net::SocketAddr, sync::Arc, time::Instant};
+
+use bytes::Bytes;
+use http::{Request, Response, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Incoming, server::conn::http1, service::service_fn};
+use opentelemetry::{
+    trace::{SpanKind, TraceContextExt},
+    Context, KeyValue,
+};
+use snafu::{ResultExt, Snafu};
+use tokio::{net::TcpListener, sync::oneshot};
+use tracing::{info, warn};
+use wasmtime_wasi_http::{
+    bindings::{
+        http::types::{ErrorCode, Scheme},
+        ProxyPre,
+    },
+    body::HyperOutgoingBody,
+    io::TokioIo,
+    WasiHttpView,
+};
+
+use crate::{
+    component::ComponentError,
+    registry::ComponentRegistry,
+    telemetry::{self, CALL_ID, COMPONENT, HTTP_METHOD, HTTP_STATUS, URL},
+};
+
+/// The interface a component must export to serve HTTP requests.
+pub const INCOMING_HANDLER_INTERFACE: &str = "wasi:http/incoming-handler";
+
+#[derive(Debug, Snafu)]
+pub enum HttpTriggerError {
+    #[snafu(display("Route '{}' must start with '/'", prefix))]
+    InvalidRoute { prefix: String },
+
+    #[snafu(display(
+        "Component '{}' does not export {}: {}",
+        name,
+        INCOMING_HANDLER_INTERFACE,
+        source
+    ))]
+    NotAnHttpHandler { name: String, source: wasmtime::Error },
+
+    #[snafu(display("No route matches '{}'", path))]
+    RouteNotFound { path: String },
+
+    #[snafu(display("{}", source))]
+    Component { source: ComponentError },
+
+    #[snafu(display("Failed to pass the request to component '{}': {}", name, source))]
+    RequestSetupFailed { name: String, source: wasmtime::Error },
+
+    #[snafu(display("Component '{}' failed to handle the request: {}", name, source))]
+    HandlerFailed { name: String, source: wasmtime::Error },
+
+    #[snafu(display("Component '{}' returned an error response: {:?}", name, code))]
+    ErrorResponse { name: String, code: ErrorCode },
+
+    #[snafu(display("Component '{}' returned without setting a response", name))]
+    NoResponse { name: String },
+}
+
+type Result<T, E = HttpTriggerError> = core::result::Result<T, E>;
+
+/// Routes HTTP requests to the components of a registry, by path prefix.
+pub struct HttpTrigger {
+    /// The registry the routed components are registered in.
+    registry: Arc<ComponentRegistry>,
+
+    /// The routes, as path prefixes and component names, longest prefix first.
+    routes: Vec<(String, String)>,
+}
+
+impl HttpTrigger {
+    /// Creates a trigger without routes, serving components of `registry`.
+    pub fn new(registry: Arc<ComponentRegistry>) -> Self {
+        Self { registry, routes: Vec::new() }
+    }
+
+    /// Routes the requests whose path starts with `prefix` to a registered component.
+    ///
+    /// Prefixes match whole path segments, so `/hooks` matches `/hooks` and `/hooks/github` but
+    /// not `/hooksmith`. A request matching several prefixes goes to the longest one, and `/`
+    /// matches every request. Routing a prefix again replaces its component.
+    ///
+    /// The component is looked up in the registry on every request, so registering a new version
+    /// under the same name takes effect without touching the routes.
+    ///
+    /// # Parameters
+    /// - `prefix`: The path prefix, such as `/` or `/hooks`.
+    /// - `component`: The name the component is registered under.
+    ///
+    /// # Errors
+    ///
+    /// - `HttpTriggerError::InvalidRoute`: If the prefix does not start with `/`.
+    /// - `HttpTriggerError::Component`: If no component is registered under the name.
+    /// - `HttpTriggerError::NotAnHttpHandler`: If the component does not export
+    ///   `wasi:http/incoming-handler`.
+    pub fn route(&mut self, prefix: impl Into<String>, component: &str) -> Result<&mut Self> {
+        let prefix = prefix.into();
+        if !prefix.starts_with('/') {
+            return Err(HttpTriggerError::InvalidRoute { prefix });
+        }
+        let registered = self.registry.get(component).context(ComponentSnafu)?;
+        ProxyPre::new(registered.pre().instance_pre().clone())
+            .context(NotAnHttpHandlerSnafu { name: component })?;
+
+        let prefix = match prefix.trim_end_matches('/') {
+            "" => "/".to_string(),
+            trimmed => trimmed.to_string(),
+        };
+        self.routes.retain(|(existing, _)| *existing != prefix);
+        self.routes.push((prefix, component.to_string()));
+        self.routes.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+        Ok(self)
+    }
+
+    /// Returns the routes, as path prefixes and component names, longest prefix first.
+    pub fn routes(&self) -> &[(String, String)] {
+        &self.routes
+    }
+
+    /// Accepts connections on `listener` and serves their requests over HTTP/1.1.
+    ///
+    /// Each connection is served in its own task. The future only completes if accepting
+    /// connections fails, and dropping it stops accepting new connections.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the listener when it stops accepting connections.
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) -> std::io::Result<()> {
+        if let Ok(addr) = listener.local_addr() {
+            info!("Serving HTTP on {}", addr);
+        }
+        loop {
+            let (stream, client) = listener.accept().await?;
+            tokio::spawn(self.clone().serve_connection(stream, client));
+        }
+    }
+
+    /// Handles a request with the component of the matching route.
+    ///
+    /// # Returns
+    ///
+    /// The response of the component, or an empty response with a status describing why the
+    /// request could not be handled: `404` when no route matches, `503` when the component is at
+    /// capacity, and `500` when the component fails.
+    pub async fn handle(&self, request: Request<Incoming>) -> Response<HyperOutgoingBody> {
+        let started = Instant::now();
+        let method = request.method().to_string();
+        let path = request.uri().path().to_string();
+        let cx = telemetry::start_span(
+            method.clone(),
+            SpanKind::Server,
+            vec![
+                KeyValue::new(HTTP_METHOD, method.clone()),
+                KeyValue::new(URL, request.uri().to_string()),
+            ],
+            &Context::current(),
+        );
+
+        let response = match self.dispatch(request, &cx).await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Failed to handle {} {}: {}", method, path, e);
+                status_response(error_status(&e))
+            }
+        };
+
+        let status = response.status();
+        cx.span().set_attribute(KeyValue::new(HTTP_STATUS, i64::from(status.as_u16())));
+        let error = status.is_server_error().then_some(&status as &dyn Display);
+        telemetry::end_span(&cx, started, error);
+        response
+    }
+
+    /// Serves the requests of a connection until the client closes it.
+    async fn serve_connection(self: Arc<Self>, stream: tokio::net::TcpStream, client: SocketAddr) {
+        let service = service_fn(move |request| {
+            let trigger = self.clone();
+            async move { Ok::<_, std::convert::Infallible>(trigger.handle(request).await) }
+        });
+        if let Err(e) = http1::Builder::new()
+            .keep_alive(true)
+            .serve_connection(TokioIo::new(stream), service)
+            .await
+        {
+            warn!("Failed to serve connection from {}: {}", client, e);
+        }
+    }
+
+    /// Runs the component of the matching route on a request.
+    async fn dispatch(
+        &self,
+        request: Request<Incoming>,
+        cx: &Context,
+    ) -> Result<Response<HyperOutgoingBody>> {
+        let path = request.uri().path();
+        let name = self
+            .routes
+            .iter()
+            .find(|(prefix, _)| prefix_matches(prefix, path))
+            .map(|(_, name)| name.clone())
+            .ok_or_else(|| HttpTriggerError::RouteNotFound { path: path.to_string() })?;
+        cx.span().set_attribute(KeyValue::new(COMPONENT, name.clone()));
+
+        let registered = self.registry.get(&name).context(ComponentSnafu)?;
+        let runtime = self.registry.runtime();
+        let permit = runtime.admission.acquire(&name).await.context(ComponentSnafu)?;
+        let pre = registered.pre();
+        let proxy_pre = ProxyPre::new(pre.instance_pre().clone())
+            .context(NotAnHttpHandlerSnafu { name: &name })?;
+        let mut store = pre.new_store(runtime).context(ComponentSnafu)?;
+        store.data_mut().set_trace_context(cx.clone());
+        let call_id = store.data_mut().begin_call();
+        cx.span().set_attribute(KeyValue::new(CALL_ID, call_id as i64));
+
+        let (sender, receiver) = oneshot::channel();
+        let state = store.data_mut();
+        let incoming = state
+            .new_incoming_request(Scheme::Http, request)
+            .context(RequestSetupFailedSnafu { name: &name })?;
+        let outparam =
+            state.new_response_outparam(sender).context(RequestSetupFailedSnafu { name: &name })?;
+
+        // The component may keep streaming the body after it set the response, so it runs in
+        // its own task, which holds the admission permit until the component returns
+        let task = tokio::spawn(async move {
+            let _permit = permit;
+            let result = match proxy_pre.instantiate_async(&mut store).await {
+                Ok(proxy) => {
+                    let handler = proxy.wasi_http_incoming_handler();
+                    handler.call_handle(&mut store, incoming, outparam).await
+                }
+                Err(e) => Err(e),
+            };
+            store.data().end_call();
+            result
+        });
+
+        match receiver.await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(code)) => Err(HttpTriggerError::ErrorResponse { name, code }),
+            // The sender was dropped with the store, without a response being set
+            Err(_) => match task.await {
+                Ok(Err(source)) => Err(HttpTriggerError::HandlerFailed { name, source }),
+                Ok(Ok(())) => Err(HttpTriggerError::NoResponse { name }),
+                Err(e) => Err(HttpTriggerError::HandlerFailed { name, source: e.into() }),
+            },
+        }
+    }
+}
+
+/// Returns whether a route prefix matches a request path, on whole path segments.
+fn prefix_matches(prefix: &str, path: &str) -> bool {
+    prefix == "/"
+        || path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Returns the status of the response to a request that could not be handled.
+fn error_status(error: &HttpTriggerError) -> StatusCode {
+    match error {
+        HttpTriggerError::RouteNotFound { .. } => StatusCode::NOT_FOUND,
+        HttpTriggerError::Component { source: ComponentError::Overloaded { .. } } => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Creates an empty response with the given status.
+fn status_response(status: StatusCode) -> Response<HyperOutgoingBody> {
+    let body = Full::new(Bytes::new()).map_err(|never| match never {}).boxed();
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,263 @@
+//! Integrity checks of component binaries.
+//!
+//! A component can be pinned to the sha256 digest of a reviewed build, or carry a detached
+//! ed25519 signature that one of the runtime's trusted keys must verify. The check runs on the
+//! exact bytes handed to the builder, before anything parses or compiles them, so a binary that
+//! fails it is never run. With `Runtime::require_integrity` set, unverified components are refused
+//! as well.
+
+use core::fmt;
+
+use ed25519_dalek::{Signature, VerifyingKey, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+use snafu::Snafu;
+
+use crate::cache::hex;
+
+/// The extension of detached signature files, which sit next to the binary they sign.
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+#[derive(Debug, Clone, PartialEq, Eq, Snafu)]
+pub enum IntegrityError {
+    #[snafu(display("the runtime requires a pinned digest or a signature"))]
+    Missing,
+
+    #[snafu(display("pinned digest '{}' is not a hex-encoded sha256", digest))]
+    InvalidDigest { digest: String },
+
+    #[snafu(display("sha256 is {}, but {} is pinned", actual, expected))]
+    DigestMismatch { expected: String, actual: String },
+
+    #[snafu(display("signature is {} bytes, expected {}", length, SIGNATURE_LENGTH))]
+    InvalidSignature { length: usize },
+
+    #[snafu(display("'{}' is not a hex-encoded ed25519 public key", key))]
+    InvalidKey { key: String },
+
+    #[snafu(display("the runtime trusts no signing key"))]
+    NoTrustedKeys,
+
+    #[snafu(display("signature is not from a trusted key"))]
+    UntrustedSignature,
+}
+
+type Result<T, E = IntegrityError> = core::result::Result<T, E>;
+
+/// How a component binary is verified before it is loaded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Integrity {
+    /// The binary is not verified. Refused when the runtime requires integrity.
+    #[default]
+    Unverified,
+
+    /// The binary must have this sha256 digest, as a hex string.
+    Sha256(String),
+
+    /// A detached ed25519 signature of the binary, which one of the runtime's trusted keys must
+    /// verify.
+    Signature(Vec<u8>),
+}
+
+/// An ed25519 public key whose signatures the runtime accepts.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct TrustedKey(VerifyingKey);
+
+impl TrustedKey {
+    /// Creates a trusted key from the 32 bytes of an ed25519 public key.
+    ///
+    /// # Errors
+    ///
+    /// Returns `IntegrityError::InvalidKey` if the bytes are not a valid public key.
+    pub fn from_bytes(bytes: &[u8; PUBLIC_KEY_LENGTH]) -> Result<Self> {
+        VerifyingKey::from_bytes(bytes)
+            .map(Self)
+            .map_err(|_| IntegrityError::InvalidKey { key: hex(bytes) })
+    }
+
+    /// Creates a trusted key from an ed25519 public key encoded as 64 hex characters.
+    ///
+    /// # Errors
+    ///
+    /// Returns `IntegrityError::InvalidKey` if the string is not a valid hex-encoded public key.
+    pub fn from_hex(key: &str) -> Result<Self> {
+        let invalid = || IntegrityError::InvalidKey { key: key.to_string() };
+        let bytes = unhex(key).ok_or_else(invalid)?;
+        let bytes: [u8; PUBLIC_KEY_LENGTH] = bytes.try_into().map_err(|_| invalid())?;
+        Self::from_bytes(&bytes).map_err(|_| invalid())
+    }
+}
+
+impl fmt::Debug for TrustedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TrustedKey").field(&hex(self.0.as_bytes())).finish()
+    }
+}
+
+impl Integrity {
+    /// Verifies a component binary.
+    ///
+    /// # Parameters
+    /// - `wasm`: The binary, as handed to the builder.
+    /// - `digest`: The sha256 of `wasm`, as a lowercase hex string.
+    /// - `trusted_keys`: The keys signatures are checked against.
+    /// - `required`: Whether unverified binaries are refused.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `IntegrityError` describing why the binary is refused.
+    pub(crate) fn verify(
+        &self,
+        wasm: &[u8],
+        digest: &str,
+        trusted_keys: &[TrustedKey],
+        required: bool,
+    ) -> Result<()> {
+        match self {
+            Integrity::Unverified if required => Err(IntegrityError::Missing),
+            Integrity::Unverified => Ok(()),
+            Integrity::Sha256(expected) => {
+                if expected.len() != 64 || unhex(expected).is_none() {
+                    return Err(IntegrityError::InvalidDigest { digest: expected.clone() });
+                }
+                match expected.eq_ignore_ascii_case(digest) {
+                    true => Ok(()),
+                    false => Err(IntegrityError::DigestMismatch {
+                        expected: expected.clone(),
+                        actual: digest.to_string(),
+                    }),
+                }
+            }
+            Integrity::Signature(signature) => {
+                let signature = Signature::from_slice(signature)
+                    .map_err(|_| IntegrityError::InvalidSignature { length: signature.len() })?;
+                if trusted_keys.is_empty() {
+                    return Err(IntegrityError::NoTrustedKeys);
+                }
+                match trusted_keys.iter().any(|key| key.0.verify_strict(wasm, &signature).is_ok()) {
+                    true => Ok(()),
+                    false => Err(IntegrityError::UntrustedSignature),
+                }
+            }
+        }
+    }
+}
+
+/// Decodes a hex string, returning `None` if it is not valid hex.
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Signer, SigningKey};
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    const WASM: &[u8] = b"\0asm\x0d\0\x01\0";
+
+    /// Returns the lowercase hex sha256 of `wasm`.
+    fn digest(wasm: &[u8]) -> String {
+        hex(&Sha256::digest(wasm))
+    }
+
+    /// Returns a signing key derived from `seed`, and the trusted key verifying its signatures.
+    fn keys(seed: u8) -> (SigningKey, TrustedKey) {
+        let signing = SigningKey::from_bytes(&[seed; 32]);
+        let trusted = TrustedKey::from_bytes(signing.verifying_key().as_bytes()).unwrap();
+        (signing, trusted)
+    }
+
+    #[test]
+    fn unverified_binaries_pass_unless_required() {
+        assert_eq!(Integrity::Unverified.verify(WASM, &digest(WASM), &[], false), Ok(()));
+        assert_eq!(
+            Integrity::Unverified.verify(WASM, &digest(WASM), &[], true),
+            Err(IntegrityError::Missing)
+        );
+    }
+
+    #[test]
+    fn pinned_digest_must_match() {
+        let pinned = Integrity::Sha256(digest(WASM).to_uppercase());
+        assert_eq!(pinned.verify(WASM, &digest(WASM), &[], true), Ok(()));
+
+        let other = digest(b"other");
+        assert_eq!(
+            pinned.verify(b"other", &other, &[], true),
+            Err(IntegrityError::DigestMismatch {
+                expected: digest(WASM).to_uppercase(),
+                actual: other,
+            })
+        );
+    }
+
+    #[test]
+    fn pinned_digest_must_be_a_sha256() {
+        for pinned in ["abcd", &"g".repeat(64), &"a".repeat(66)] {
+            assert_eq!(
+                Integrity::Sha256(pinned.to_string()).verify(WASM, &digest(WASM), &[], false),
+                Err(IntegrityError::InvalidDigest { digest: pinned.to_string() })
+            );
+        }
+    }
+
+    #[test]
+    fn signature_of_any_trusted_key_passes() {
+        let (signing, trusted) = keys(1);
+        let (_, other) = keys(2);
+        let signature = Integrity::Signature(signing.sign(WASM).to_bytes().to_vec());
+        assert_eq!(signature.verify(WASM, &digest(WASM), &[other, trusted], true), Ok(()));
+        assert_eq!(
+            signature.verify(WASM, &digest(WASM), &[other], true),
+            Err(IntegrityError::UntrustedSignature)
+        );
+        assert_eq!(
+            signature.verify(b"tampered", &digest(b"tampered"), &[trusted], true),
+            Err(IntegrityError::UntrustedSignature)
+        );
+        assert_eq!(
+            signature.verify(WASM, &digest(WASM), &[], true),
+            Err(IntegrityError::NoTrustedKeys)
+        );
+    }
+
+    #[test]
+    fn signature_must_have_the_ed25519_length() {
+        let (_, trusted) = keys(1);
+        assert_eq!(
+            Integrity::Signature(vec![0; 3]).verify(WASM, &digest(WASM), &[trusted], true),
+            Err(IntegrityError::InvalidSignature { length: 3 })
+        );
+    }
+
+    #[test]
+    fn trusted_keys_are_parsed_from_hex() {
+        let (signing, trusted) = keys(1);
+        let key = hex(signing.verifying_key().as_bytes());
+        assert_eq!(TrustedKey::from_hex(&key), Ok(trusted));
+        assert_eq!(TrustedKey::from_hex(&key.to_uppercase()), Ok(trusted));
+        for key in [&key[2..], "zz", "not a key"] {
+            assert_eq!(
+                TrustedKey::from_hex(key),
+                Err(IntegrityError::InvalidKey { key: key.to_string() })
+            );
+        }
+    }
+
+    #[test]
+    fn unhex_decodes_pairs_of_hex_digits() {
+        assert_eq!(unhex(""), Some(vec![]));
+        assert_eq!(unhex("00ff7F"), Some(vec![0x00, 0xff, 0x7f]));
+        assert_eq!(unhex("abc"), None);
+        assert_eq!(unhex("0g"), None);
+        assert_eq!(unhex("+1"), None);
+        // Multi-byte characters are rejected rather than split
+        assert_eq!(unhex("é"), None);
+    }
+}
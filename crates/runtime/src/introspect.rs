@@ -0,0 +1,366 @@
+//! Introspection of the imports and exports of a component.
+//!
+//! This module turns the types reported by Wasmtime into a plain tree of interfaces, functions
+//! and types, which can be inspected by tooling or rendered as WIT text.
+//!
+//! Component types are structural, so only types exported by name (such as the records of an
+//! interface) keep their name. Types used inside function signatures are rendered inline, e.g.
+//! `record { role: string, content: string }`, which makes the rendered text WIT-style rather than
+//! a document `wit-parser` accepts.
+
+use core::fmt::{self, Display, Write};
+
+use wasmtime::{
+    component::types::{self, ComponentItem, Type},
+    Engine,
+};
+
+/// An item imported or exported by a component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItemDescriptor {
+    /// A component function.
+    Function(FunctionDescriptor),
+    /// An instance, usually an interface such as `wasi:http/types@0.2.0`.
+    Interface(InterfaceDescriptor),
+    /// A named type.
+    Type { name: String, ty: TypeDescriptor },
+    /// A resource type.
+    Resource { name: String },
+    /// A core WebAssembly module.
+    Module { name: String },
+    /// A nested component.
+    Component { name: String },
+    /// A core WebAssembly function.
+    CoreFunction { name: String },
+}
+
+impl ItemDescriptor {
+    /// Returns the name the item is imported or exported under.
+    pub fn name(&self) -> &str {
+        match self {
+            ItemDescriptor::Function(function) => &function.name,
+            ItemDescriptor::Interface(interface) => &interface.name,
+            ItemDescriptor::Type { name, .. }
+            | ItemDescriptor::Resource { name }
+            | ItemDescriptor::Module { name }
+            | ItemDescriptor::Component { name }
+            | ItemDescriptor::CoreFunction { name } => name,
+        }
+    }
+
+    /// Describes a component item reported by Wasmtime.
+    pub(crate) fn new(engine: &Engine, name: &str, item: &ComponentItem) -> Self {
+        let name = name.to_string();
+        match item {
+            ComponentItem::ComponentFunc(func) => {
+                ItemDescriptor::Function(FunctionDescriptor::new(name, func))
+            }
+            ComponentItem::ComponentInstance(instance) => {
+                ItemDescriptor::Interface(InterfaceDescriptor {
+                    items: instance
+                        .exports(engine)
+                        .map(|(name, item)| ItemDescriptor::new(engine, name, &item))
+                        .collect(),
+                    name,
+                })
+            }
+            ComponentItem::Type(ty) => ItemDescriptor::Type { name, ty: TypeDescriptor::from(ty) },
+            ComponentItem::Resource(_) => ItemDescriptor::Resource { name },
+            ComponentItem::Module(_) => ItemDescriptor::Module { name },
+            ComponentItem::Component(_) => ItemDescriptor::Component { name },
+            ComponentItem::CoreFunc(_) => ItemDescriptor::CoreFunction { name },
+        }
+    }
+
+    /// Writes the item as a WIT-style declaration, indented by `indent` levels.
+    fn write_wit(&self, f: &mut impl Write, indent: usize) -> fmt::Result {
+        let pad = "  ".repeat(indent);
+        match self {
+            ItemDescriptor::Function(function) => writeln!(f, "{}{};", pad, function),
+            ItemDescriptor::Interface(interface) => {
+                writeln!(f, "{}interface {} {{", pad, interface.name)?;
+                for item in &interface.items {
+                    item.write_wit(f, indent + 1)?;
+                }
+                writeln!(f, "{}}}", pad)
+            }
+            ItemDescriptor::Type { name, ty } => match ty {
+                TypeDescriptor::Record(_)
+                | TypeDescriptor::Variant(_)
+                | TypeDescriptor::Enum(_)
+                | TypeDescriptor::Flags(_) => {
+                    let definition = ty.to_string();
+                    let (keyword, body) = definition.split_once(' ').unwrap_or((&definition, ""));
+                    writeln!(f, "{}{} {} {}", pad, keyword, name, body)
+                }
+                _ => writeln!(f, "{}type {} = {};", pad, name, ty),
+            },
+            ItemDescriptor::Resource { name } => writeln!(f, "{}resource {};", pad, name),
+            ItemDescriptor::Module { name } => writeln!(f, "{}/* core module {} */", pad, name),
+            ItemDescriptor::Component { name } => writeln!(f, "{}/* component {} */", pad, name),
+            ItemDescriptor::CoreFunction { name } => {
+                writeln!(f, "{}/* core function {} */", pad, name)
+            }
+        }
+    }
+}
+
+/// An instance imported or exported by a component, with the items it contains.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceDescriptor {
+    /// The name of the instance, e.g. `pawn:chat/handler@0.1.0`.
+    pub name: String,
+
+    /// The items exported by the instance.
+    pub items: Vec<ItemDescriptor>,
+}
+
+/// The signature of a component function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionDescriptor {
+    /// The name of the function.
+    pub name: String,
+
+    /// The names and types of the parameters.
+    pub params: Vec<(String, TypeDescriptor)>,
+
+    /// The types of the results.
+    pub results: Vec<TypeDescriptor>,
+}
+
+impl FunctionDescriptor {
+    /// Describes a component function type reported by Wasmtime.
+    pub(crate) fn new(name: String, func: &types::ComponentFunc) -> Self {
+        Self {
+            name,
+            params: func
+                .params()
+                .map(|(name, ty)| (name.to_string(), TypeDescriptor::from(&ty)))
+                .collect(),
+            results: func.results().map(|ty| TypeDescriptor::from(&ty)).collect(),
+        }
+    }
+}
+
+impl Display for FunctionDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: func(", self.name)?;
+        for (i, (name, ty)) in self.params.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", name, ty)?;
+        }
+        write!(f, ")")?;
+        match self.results.as_slice() {
+            [] => Ok(()),
+            [result] => write!(f, " -> {}", result),
+            results => write!(f, " -> {}", TypeDescriptor::Tuple(results.to_vec())),
+        }
+    }
+}
+
+/// A component model value type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeDescriptor {
+    Bool,
+    S8,
+    U8,
+    S16,
+    U16,
+    S32,
+    U32,
+    S64,
+    U64,
+    F32,
+    F64,
+    Char,
+    String,
+    List(Box<TypeDescriptor>),
+    Record(Vec<(String, TypeDescriptor)>),
+    Tuple(Vec<TypeDescriptor>),
+    Variant(Vec<(String, Option<TypeDescriptor>)>),
+    Enum(Vec<String>),
+    Option(Box<TypeDescriptor>),
+    Result { ok: Option<Box<TypeDescriptor>>, err: Option<Box<TypeDescriptor>> },
+    Flags(Vec<String>),
+    Own,
+    Borrow,
+}
+
+impl From<&Type> for TypeDescriptor {
+    fn from(ty: &Type) -> Self {
+        match ty {
+            Type::Bool => TypeDescriptor::Bool,
+            Type::S8 => TypeDescriptor::S8,
+            Type::U8 => TypeDescriptor::U8,
+            Type::S16 => TypeDescriptor::S16,
+            Type::U16 => TypeDescriptor::U16,
+            Type::S32 => TypeDescriptor::S32,
+            Type::U32 => TypeDescriptor::U32,
+            Type::S64 => TypeDescriptor::S64,
+            Type::U64 => TypeDescriptor::U64,
+            Type::Float32 => TypeDescriptor::F32,
+            Type::Float64 => TypeDescriptor::F64,
+            Type::Char => TypeDescriptor::Char,
+            Type::String => TypeDescriptor::String,
+            Type::List(list) => TypeDescriptor::List(Box::new((&list.ty()).into())),
+            Type::Record(record) => TypeDescriptor::Record(
+                record.fields().map(|field| (field.name.to_string(), (&field.ty).into())).collect(),
+            ),
+            Type::Tuple(tuple) => {
+                TypeDescriptor::Tuple(tuple.types().map(|ty| (&ty).into()).collect())
+            }
+            Type::Variant(variant) => TypeDescriptor::Variant(
+                variant
+                    .cases()
+                    .map(|case| (case.name.to_string(), case.ty.as_ref().map(Into::into)))
+                    .collect(),
+            ),
+            Type::Enum(enum_) => TypeDescriptor::Enum(enum_.names().map(str::to_string).collect()),
+            Type::Option(option) => TypeDescriptor::Option(Box::new((&option.ty()).into())),
+            Type::Result(result) => TypeDescriptor::Result {
+                ok: result.ok().map(|ty| Box::new((&ty).into())),
+                err: result.err().map(|ty| Box::new((&ty).into())),
+            },
+            Type::Flags(flags) => {
+                TypeDescriptor::Flags(flags.names().map(str::to_string).collect())
+            }
+            Type::Own(_) => TypeDescriptor::Own,
+            Type::Borrow(_) => TypeDescriptor::Borrow,
+        }
+    }
+}
+
+impl Display for TypeDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeDescriptor::Bool => write!(f, "bool"),
+            TypeDescriptor::S8 => write!(f, "s8"),
+            TypeDescriptor::U8 => write!(f, "u8"),
+            TypeDescriptor::S16 => write!(f, "s16"),
+            TypeDescriptor::U16 => write!(f, "u16"),
+            TypeDescriptor::S32 => write!(f, "s32"),
+            TypeDescriptor::U32 => write!(f, "u32"),
+            TypeDescriptor::S64 => write!(f, "s64"),
+            TypeDescriptor::U64 => write!(f, "u64"),
+            TypeDescriptor::F32 => write!(f, "f32"),
+            TypeDescriptor::F64 => write!(f, "f64"),
+            TypeDescriptor::Char => write!(f, "char"),
+            TypeDescriptor::String => write!(f, "string"),
+            TypeDescriptor::List(ty) => write!(f, "list<{}>", ty),
+            TypeDescriptor::Record(fields) => {
+                write!(f, "record {{ ")?;
+                write_separated(f, fields.iter().map(|(name, ty)| format!("{}: {}", name, ty)))?;
+                write!(f, " }}")
+            }
+            TypeDescriptor::Tuple(types) => {
+                write!(f, "tuple<")?;
+                write_separated(f, types.iter().map(ToString::to_string))?;
+                write!(f, ">")
+            }
+            TypeDescriptor::Variant(cases) => {
+                write!(f, "variant {{ ")?;
+                write_separated(
+                    f,
+                    cases.iter().map(|(name, ty)| match ty {
+                        Some(ty) => format!("{}({})", name, ty),
+                        None => name.clone(),
+                    }),
+                )?;
+                write!(f, " }}")
+            }
+            TypeDescriptor::Enum(names) => {
+                write!(f, "enum {{ ")?;
+                write_separated(f, names.iter().cloned())?;
+                write!(f, " }}")
+            }
+            TypeDescriptor::Option(ty) => write!(f, "option<{}>", ty),
+            TypeDescriptor::Result { ok, err } => match (ok, err) {
+                (None, None) => write!(f, "result"),
+                (Some(ok), None) => write!(f, "result<{}>", ok),
+                (None, Some(err)) => write!(f, "result<_, {}>", err),
+                (Some(ok), Some(err)) => write!(f, "result<{}, {}>", ok, err),
+            },
+            TypeDescriptor::Flags(names) => {
+                write!(f, "flags {{ ")?;
+                write_separated(f, names.iter().cloned())?;
+                write!(f, " }}")
+            }
+            TypeDescriptor::Own => write!(f, "own<resource>"),
+            TypeDescriptor::Borrow => write!(f, "borrow<resource>"),
+        }
+    }
+}
+
+/// The imports and exports of a component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentDescriptor {
+    /// The items the component imports from the host.
+    pub imports: Vec<ItemDescriptor>,
+
+    /// The items the component exports.
+    pub exports: Vec<ItemDescriptor>,
+}
+
+impl ComponentDescriptor {
+    /// Describes the imports and exports of a compiled component.
+    pub(crate) fn new(component: &wasmtime::component::Component) -> Self {
+        let engine = component.engine();
+        let ty = component.component_type();
+        Self {
+            imports: ty
+                .imports(engine)
+                .map(|(name, item)| ItemDescriptor::new(engine, name, &item))
+                .collect(),
+            exports: ty
+                .exports(engine)
+                .map(|(name, item)| ItemDescriptor::new(engine, name, &item))
+                .collect(),
+        }
+    }
+
+    /// Renders the component as a WIT-style world named `name`.
+    pub fn to_wit(&self, name: &str) -> String {
+        let mut wit = String::new();
+        // Writing to a `String` cannot fail
+        let _ = self.write_wit(&mut wit, name);
+        wit
+    }
+
+    fn write_wit(&self, f: &mut impl Write, name: &str) -> fmt::Result {
+        writeln!(f, "world {} {{", name)?;
+        for (keyword, items) in [("import", &self.imports), ("export", &self.exports)] {
+            for item in items {
+                match item {
+                    ItemDescriptor::Interface(interface) => {
+                        writeln!(f, "  {} {}: interface {{", keyword, interface.name)?;
+                        for item in &interface.items {
+                            item.write_wit(f, 2)?;
+                        }
+                        writeln!(f, "  }}")?;
+                    }
+                    ItemDescriptor::Function(function) => {
+                        writeln!(f, "  {} {};", keyword, function)?;
+                    }
+                    item => item.write_wit(f, 1)?,
+                }
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// Writes items separated by `, `.
+fn write_separated(
+    f: &mut fmt::Formatter<'_>,
+    items: impl IntoIterator<Item = String>,
+) -> fmt::Result {
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,763 @@
+//! Conversion between JSON and component model values, guided by the WIT types of a function.
+//!
+//! JSON values map to the component model as follows:
+//!
+//! - booleans, numbers and strings map to `bool`, the integer and float types, `char` and
+//!   `string`. Non-finite floats are written `"NaN"`, `"Infinity"` and `"-Infinity"`;
+//! - records are objects keyed by field name, where missing `option` fields are `none`;
+//! - lists, tuples and flags are arrays, flags holding the names of the set flags;
+//! - enums are strings, and variants are `"case"` or `{ "case": payload }`;
+//! - options are `null` or their value, and results are `{ "ok": value }` or `{ "err": value }`.
+//!
+//! Errors point at the offending value with a JSONPath-style path, such as `$.messages[2].role`.
+
+use core::fmt::{self, Display};
+
+use serde_json::{Map, Number, Value};
+use snafu::Snafu;
+use wasmtime::component::{types, Type, Val};
+
+#[derive(Debug, Clone, PartialEq, Snafu)]
+pub enum JsonError {
+    #[snafu(display("{}: expected {}, found {}", path, expected, found))]
+    TypeMismatch { path: String, expected: String, found: String },
+
+    #[snafu(display("{}: {} is out of range for {}", path, value, ty))]
+    IntegerOutOfRange { path: String, ty: String, value: Number },
+
+    #[snafu(display("{}: missing field '{}'", path, field))]
+    MissingField { path: String, field: String },
+
+    #[snafu(display("{}: unknown field '{}', expected one of {}", path, field, expected.join(", ")))]
+    UnknownField { path: String, field: String, expected: Vec<String> },
+
+    #[snafu(display("{}: unknown case '{}', expected one of {}", path, case, expected.join(", ")))]
+    UnknownCase { path: String, case: String, expected: Vec<String> },
+
+    #[snafu(display("{}: case '{}' requires a payload", path, case))]
+    MissingPayload { path: String, case: String },
+
+    #[snafu(display("{}: case '{}' has no payload", path, case))]
+    UnexpectedPayload { path: String, case: String },
+
+    #[snafu(display("{}: expected {} items, found {}", path, expected, found))]
+    LengthMismatch { path: String, expected: usize, found: usize },
+
+    #[snafu(display("{}: resources cannot be converted to or from JSON", path))]
+    UnsupportedResource { path: String },
+}
+
+type Result<T, E = JsonError> = core::result::Result<T, E>;
+
+/// Converts a JSON value to a component value of type `ty`.
+///
+/// # Parameters
+/// - `ty`: The type of the value, usually taken from a function signature.
+/// - `value`: The JSON value to convert.
+///
+/// # Returns
+///
+/// A `Result` containing the component value.
+///
+/// # Errors
+///
+/// Returns a `JsonError` locating the first part of `value` that does not match `ty`.
+pub fn val_from_json(ty: &Type, value: &Value) -> Result<Val> {
+    from_json(ty, value, &Path::Root)
+}
+
+/// Converts the JSON arguments of a call to the parameters of `func`.
+///
+/// # Parameters
+/// - `func`: The type of the function to call.
+/// - `args`: An array of arguments in parameter order, or an object keyed by parameter name.
+///
+/// # Returns
+///
+/// A `Result` containing the values to pass to the function.
+///
+/// # Errors
+///
+/// Returns a `JsonError` locating the first argument that does not match its parameter, or the
+/// arguments themselves when their number or names do not match the parameters.
+pub fn params_from_json(func: &types::ComponentFunc, args: &Value) -> Result<Vec<Val>> {
+    let root = Path::Root;
+    match args {
+        Value::Array(args) => {
+            if args.len() != func.params().len() {
+                return Err(JsonError::LengthMismatch {
+                    path: root.to_string(),
+                    expected: func.params().len(),
+                    found: args.len(),
+                });
+            }
+            func.params()
+                .zip(args)
+                .enumerate()
+                .map(|(i, ((_, ty), arg))| from_json(&ty, arg, &Path::Index(&root, i)))
+                .collect()
+        }
+        Value::Object(args) => {
+            let names: Vec<&str> = func.params().map(|(name, _)| name).collect();
+            check_fields(args, &names, &root)?;
+            func.params()
+                .map(|(name, ty)| match args.get(name) {
+                    Some(arg) => from_json(&ty, arg, &Path::Field(&root, name)),
+                    None => Err(JsonError::MissingField {
+                        path: root.to_string(),
+                        field: name.to_string(),
+                    }),
+                })
+                .collect()
+        }
+        _ => mismatch("an array or object of arguments", args, &root),
+    }
+}
+
+/// Converts a component value to JSON.
+///
+/// # Errors
+///
+/// Returns an error if the value holds a resource, which has no JSON form.
+pub fn val_to_json(val: &Val) -> Result<Value> {
+    to_json(val, &Path::Root)
+}
+
+/// Converts the results of a call to JSON: the value of a single result, or an array of them.
+///
+/// # Errors
+///
+/// Returns an error if a result holds a resource, which has no JSON form.
+pub fn results_to_json(results: &[Val]) -> Result<Value> {
+    match results {
+        [result] => val_to_json(result),
+        results => results
+            .iter()
+            .enumerate()
+            .map(|(index, result)| to_json(result, &Path::Index(&Path::Root, index)))
+            .collect(),
+    }
+}
+
+/// The location of a value inside the JSON document being converted.
+enum Path<'a> {
+    /// The document itself.
+    Root,
+
+    /// A field of an object.
+    Field(&'a Path<'a>, &'a str),
+
+    /// An item of an array.
+    Index(&'a Path<'a>, usize),
+}
+
+impl Display for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Path::Root => write!(f, "$"),
+            Path::Field(parent, name) => write!(f, "{}.{}", parent, name),
+            Path::Index(parent, index) => write!(f, "{}[{}]", parent, index),
+        }
+    }
+}
+
+/// Converts the component value at `path` to JSON.
+fn to_json(val: &Val, path: &Path<'_>) -> Result<Value> {
+    let value = match val {
+        Val::Bool(b) => Value::Bool(*b),
+        Val::S8(n) => Value::from(*n),
+        Val::U8(n) => Value::from(*n),
+        Val::S16(n) => Value::from(*n),
+        Val::U16(n) => Value::from(*n),
+        Val::S32(n) => Value::from(*n),
+        Val::U32(n) => Value::from(*n),
+        Val::S64(n) => Value::from(*n),
+        Val::U64(n) => Value::from(*n),
+        Val::Float32(n) => float_to_json(f64::from(*n)),
+        Val::Float64(n) => float_to_json(*n),
+        Val::Char(c) => Value::String(c.to_string()),
+        Val::String(s) => Value::String(s.clone()),
+        Val::List(items) | Val::Tuple(items) => items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| to_json(item, &Path::Index(path, index)))
+            .collect::<Result<_>>()?,
+        Val::Record(fields) => fields
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), to_json(value, &Path::Field(path, name))?)))
+            .collect::<Result<_>>()?,
+        Val::Variant(name, None) | Val::Enum(name) => Value::String(name.clone()),
+        Val::Variant(name, Some(payload)) => Value::Object(Map::from_iter([(
+            name.clone(),
+            to_json(payload, &Path::Field(path, name))?,
+        )])),
+        Val::Option(value) => match value {
+            Some(value) => to_json(value, path)?,
+            None => Value::Null,
+        },
+        Val::Result(result) => {
+            let (name, payload) = match result {
+                Ok(payload) => ("ok", payload),
+                Err(payload) => ("err", payload),
+            };
+            let payload = match payload {
+                Some(payload) => to_json(payload, &Path::Field(path, name))?,
+                None => Value::Null,
+            };
+            Value::Object(Map::from_iter([(name.to_string(), payload)]))
+        }
+        Val::Flags(names) => Value::Array(names.iter().cloned().map(Value::String).collect()),
+        Val::Resource(_) => return Err(JsonError::UnsupportedResource { path: path.to_string() }),
+    };
+    Ok(value)
+}
+
+/// Writes a float as a number, or as the name of a non-finite float, which JSON numbers cannot
+/// hold.
+fn float_to_json(n: f64) -> Value {
+    match Number::from_f64(n) {
+        Some(n) => Value::Number(n),
+        None if n.is_nan() => Value::String("NaN".to_string()),
+        None if n > 0.0 => Value::String("Infinity".to_string()),
+        None => Value::String("-Infinity".to_string()),
+    }
+}
+
+/// Converts the value at `path` to a component value of type `ty`.
+fn from_json(ty: &Type, value: &Value, path: &Path<'_>) -> Result<Val> {
+    let val = match ty {
+        Type::Bool => match value {
+            Value::Bool(b) => Val::Bool(*b),
+            _ => return mismatch("a boolean", value, path),
+        },
+        Type::S8 => Val::S8(int(value, "s8", path)?),
+        Type::U8 => Val::U8(int(value, "u8", path)?),
+        Type::S16 => Val::S16(int(value, "s16", path)?),
+        Type::U16 => Val::U16(int(value, "u16", path)?),
+        Type::S32 => Val::S32(int(value, "s32", path)?),
+        Type::U32 => Val::U32(int(value, "u32", path)?),
+        Type::S64 => Val::S64(int(value, "s64", path)?),
+        Type::U64 => Val::U64(int(value, "u64", path)?),
+        Type::Float32 => Val::Float32(float(value, path)? as f32),
+        Type::Float64 => Val::Float64(float(value, path)?),
+        Type::Char => {
+            let mut chars = string(value, path)?.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Val::Char(c),
+                _ => return mismatch("a single character", value, path),
+            }
+        }
+        Type::String => Val::String(string(value, path)?.to_string()),
+        Type::List(list) => {
+            let ty = list.ty();
+            let items = array(value, path)?;
+            Val::List(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| from_json(&ty, item, &Path::Index(path, i)))
+                    .collect::<Result<_>>()?,
+            )
+        }
+        Type::Record(record) => {
+            let object = object(value, path)?;
+            let names: Vec<&str> = record.fields().map(|field| field.name).collect();
+            check_fields(object, &names, path)?;
+            Val::Record(
+                record
+                    .fields()
+                    .map(|field| {
+                        let value = match object.get(field.name) {
+                            Some(value) => {
+                                from_json(&field.ty, value, &Path::Field(path, field.name))?
+                            }
+                            None if matches!(field.ty, Type::Option(_)) => Val::Option(None),
+                            None => {
+                                return Err(JsonError::MissingField {
+                                    path: path.to_string(),
+                                    field: field.name.to_string(),
+                                })
+                            }
+                        };
+                        Ok((field.name.to_string(), value))
+                    })
+                    .collect::<Result<_>>()?,
+            )
+        }
+        Type::Tuple(tuple) => {
+            let items = array(value, path)?;
+            if items.len() != tuple.types().len() {
+                return Err(JsonError::LengthMismatch {
+                    path: path.to_string(),
+                    expected: tuple.types().len(),
+                    found: items.len(),
+                });
+            }
+            Val::Tuple(
+                tuple
+                    .types()
+                    .zip(items)
+                    .enumerate()
+                    .map(|(i, (ty, item))| from_json(&ty, item, &Path::Index(path, i)))
+                    .collect::<Result<_>>()?,
+            )
+        }
+        Type::Variant(variant) => {
+            let (name, payload) = case(value, path)?;
+            let names: Vec<&str> = variant.cases().map(|case| case.name).collect();
+            let ty = variant
+                .cases()
+                .find(|case| case.name == name)
+                .ok_or_else(|| unknown_case(name, &names, path))?
+                .ty;
+            Val::Variant(name.to_string(), payload_from_json(ty, name, payload, path)?)
+        }
+        Type::Enum(enum_) => {
+            let name = string(value, path)?;
+            let names: Vec<&str> = enum_.names().collect();
+            if !names.contains(&name) {
+                return Err(unknown_case(name, &names, path));
+            }
+            Val::Enum(name.to_string())
+        }
+        Type::Option(option) => match value {
+            Value::Null => Val::Option(None),
+            value => Val::Option(Some(Box::new(from_json(&option.ty(), value, path)?))),
+        },
+        Type::Result(result) => {
+            let (name, payload) = case(value, path)?;
+            match name {
+                "ok" => Val::Result(Ok(payload_from_json(result.ok(), name, payload, path)?)),
+                "err" => Val::Result(Err(payload_from_json(result.err(), name, payload, path)?)),
+                _ => return Err(unknown_case(name, &["ok", "err"], path)),
+            }
+        }
+        Type::Flags(flags) => {
+            let names: Vec<&str> = flags.names().collect();
+            let items = array(value, path)?;
+            Val::Flags(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| {
+                        let path = Path::Index(path, i);
+                        let name = string(item, &path)?;
+                        match names.contains(&name) {
+                            true => Ok(name.to_string()),
+                            false => Err(unknown_case(name, &names, &path)),
+                        }
+                    })
+                    .collect::<Result<_>>()?,
+            )
+        }
+        Type::Own(_) | Type::Borrow(_) => {
+            return Err(JsonError::UnsupportedResource { path: path.to_string() })
+        }
+    };
+    Ok(val)
+}
+
+/// Converts the payload of the case `name` of a variant or result, of type `ty` if it has one.
+///
+/// A case without a payload may be written `"case"` or `{ "case": null }`.
+fn payload_from_json(
+    ty: Option<Type>,
+    name: &str,
+    payload: Option<&Value>,
+    path: &Path<'_>,
+) -> Result<Option<Box<Val>>> {
+    match (ty, payload) {
+        (Some(ty), Some(payload)) => {
+            Ok(Some(Box::new(from_json(&ty, payload, &Path::Field(path, name))?)))
+        }
+        (None, None | Some(Value::Null)) => Ok(None),
+        (Some(_), None) => {
+            Err(JsonError::MissingPayload { path: path.to_string(), case: name.to_string() })
+        }
+        (None, Some(_)) => {
+            Err(JsonError::UnexpectedPayload { path: path.to_string(), case: name.to_string() })
+        }
+    }
+}
+
+/// Rejects the fields of `object` that are not in `names`.
+fn check_fields(object: &Map<String, Value>, names: &[&str], path: &Path<'_>) -> Result<()> {
+    match object.keys().find(|key| !names.contains(&key.as_str())) {
+        Some(field) => Err(JsonError::UnknownField {
+            path: path.to_string(),
+            field: field.clone(),
+            expected: names.iter().map(|name| name.to_string()).collect(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Returns the error of a case that is not one of `names`.
+fn unknown_case(name: &str, names: &[&str], path: &Path<'_>) -> JsonError {
+    JsonError::UnknownCase {
+        path: path.to_string(),
+        case: name.to_string(),
+        expected: names.iter().map(|name| name.to_string()).collect(),
+    }
+}
+
+/// Returns the error of a value that is not of the expected shape.
+fn mismatch<T>(expected: &str, found: &Value, path: &Path<'_>) -> Result<T> {
+    Err(JsonError::TypeMismatch {
+        path: path.to_string(),
+        expected: expected.to_string(),
+        found: kind(found).to_string(),
+    })
+}
+
+/// Returns the kind of a JSON value, as it appears in errors.
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Reads an integer that fits in `T`, the integer type named `ty`.
+fn int<T: TryFrom<i64> + TryFrom<u64>>(value: &Value, ty: &str, path: &Path<'_>) -> Result<T> {
+    let Value::Number(n) = value else {
+        return mismatch("an integer", value, path);
+    };
+    if !n.is_i64() && !n.is_u64() {
+        return mismatch("an integer", value, path);
+    }
+    n.as_i64()
+        .and_then(|n| T::try_from(n).ok())
+        .or_else(|| n.as_u64().and_then(|n| T::try_from(n).ok()))
+        .ok_or_else(|| JsonError::IntegerOutOfRange {
+            path: path.to_string(),
+            ty: ty.to_string(),
+            value: n.clone(),
+        })
+}
+
+/// Reads a float, written as a number or as one of the names of the non-finite floats.
+fn float(value: &Value, path: &Path<'_>) -> Result<f64> {
+    match value {
+        Value::Number(n) => n.as_f64().map_or_else(|| mismatch("a number", value, path), Ok),
+        Value::String(s) if s == "NaN" => Ok(f64::NAN),
+        Value::String(s) if s == "Infinity" => Ok(f64::INFINITY),
+        Value::String(s) if s == "-Infinity" => Ok(f64::NEG_INFINITY),
+        _ => mismatch("a number", value, path),
+    }
+}
+
+/// Reads a string.
+fn string<'v>(value: &'v Value, path: &Path<'_>) -> Result<&'v str> {
+    value.as_str().map_or_else(|| mismatch("a string", value, path), Ok)
+}
+
+/// Reads an array.
+fn array<'v>(value: &'v Value, path: &Path<'_>) -> Result<&'v Vec<Value>> {
+    value.as_array().map_or_else(|| mismatch("an array", value, path), Ok)
+}
+
+/// Reads an object.
+fn object<'v>(value: &'v Value, path: &Path<'_>) -> Result<&'v Map<String, Value>> {
+    value.as_object().map_or_else(|| mismatch("an object", value, path), Ok)
+}
+
+/// Reads a case written as `"name"` or `{ "name": payload }`.
+fn case<'v>(value: &'v Value, path: &Path<'_>) -> Result<(&'v str, Option<&'v Value>)> {
+    match value {
+        Value::String(name) => Ok((name, None)),
+        Value::Object(object) if object.len() == 1 => {
+            let (name, payload) = object.iter().next().expect("object has one entry");
+            Ok((name, Some(payload)))
+        }
+        _ => mismatch("a case name or an object with a single case", value, path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wasmtime::{
+        component::{types::ComponentItem, Component, Resource},
+        Engine, Store,
+    };
+
+    use super::*;
+
+    /// A component importing a function `f` with a parameter of each kind of type.
+    const TYPES: &str = r#"
+        (component
+          (import "pawn:test/types" (instance
+            (type $person (record
+              (field "name" string) (field "age" u8) (field "nick" (option string))))
+            (export "person" (type $person' (eq $person)))
+            (type $shape (variant (case "none") (case "circle" u32)))
+            (export "shape" (type $shape' (eq $shape)))
+            (type $color (enum "red" "green"))
+            (export "color" (type $color' (eq $color)))
+            (type $perms (flags "read" "write"))
+            (export "perms" (type $perms' (eq $perms)))
+            (export "f" (func
+              (param "flag" bool)
+              (param "small" u8)
+              (param "big" u64)
+              (param "ratio" float64)
+              (param "letter" char)
+              (param "text" string)
+              (param "person" $person')
+              (param "people" (list $person'))
+              (param "pair" (tuple u32 string))
+              (param "shape" $shape')
+              (param "color" $color')
+              (param "perms" $perms')
+              (param "outcome" (result u32 (error string)))))))
+        )
+    "#;
+
+    /// Returns the type of the function `f` of the test component.
+    fn func() -> types::ComponentFunc {
+        let engine = Engine::default();
+        let wasm = wat::parse_str(TYPES).unwrap();
+        let component = Component::new(&engine, wasm).unwrap();
+        let (_, ComponentItem::ComponentInstance(instance)) =
+            component.component_type().imports(&engine).next().unwrap()
+        else {
+            panic!("the test component imports an instance");
+        };
+        let (_, ComponentItem::ComponentFunc(func)) =
+            instance.exports(&engine).find(|(name, _)| *name == "f").unwrap()
+        else {
+            panic!("the test instance exports a function");
+        };
+        func
+    }
+
+    /// Returns the type of the parameter `name` of `f`.
+    fn ty(name: &str) -> Type {
+        func().params().find(|(param, _)| *param == name).unwrap().1
+    }
+
+    /// Converts `value` to the type of the parameter `name` and back to JSON.
+    fn round_trip(name: &str, value: Value) -> Value {
+        val_to_json(&val_from_json(&ty(name), &value).unwrap()).unwrap()
+    }
+
+    /// Returns the error of a value at `path` that is not of the expected shape.
+    fn mismatch_at(path: &str, expected: &str, found: &str) -> JsonError {
+        JsonError::TypeMismatch {
+            path: path.to_string(),
+            expected: expected.to_string(),
+            found: found.to_string(),
+        }
+    }
+
+    #[test]
+    fn primitives_round_trip() {
+        for (name, value) in [
+            ("flag", json!(true)),
+            ("small", json!(255)),
+            ("big", json!(u64::MAX)),
+            ("ratio", json!(0.5)),
+            ("letter", json!("é")),
+            ("text", json!("hello")),
+        ] {
+            assert_eq!(round_trip(name, value.clone()), value, "{}", name);
+        }
+    }
+
+    #[test]
+    fn non_finite_floats_round_trip_as_names() {
+        assert_eq!(
+            val_from_json(&ty("ratio"), &json!("Infinity")),
+            Ok(Val::Float64(f64::INFINITY))
+        );
+        assert!(matches!(
+            val_from_json(&ty("ratio"), &json!("NaN")),
+            Ok(Val::Float64(n)) if n.is_nan()
+        ));
+        for name in ["NaN", "Infinity", "-Infinity"] {
+            assert_eq!(round_trip("ratio", json!(name)), json!(name));
+        }
+        assert_eq!(val_to_json(&Val::Float32(f32::NEG_INFINITY)), Ok(json!("-Infinity")));
+    }
+
+    #[test]
+    fn integers_are_range_checked() {
+        assert_eq!(
+            val_from_json(&ty("small"), &json!(256)),
+            Err(JsonError::IntegerOutOfRange {
+                path: "$".to_string(),
+                ty: "u8".to_string(),
+                value: Number::from(256),
+            })
+        );
+        assert_eq!(
+            val_from_json(&ty("small"), &json!(1.5)),
+            Err(mismatch_at("$", "an integer", "a number"))
+        );
+        assert_eq!(
+            val_from_json(&ty("big"), &json!("1")),
+            Err(mismatch_at("$", "an integer", "a string"))
+        );
+    }
+
+    #[test]
+    fn chars_hold_a_single_character() {
+        assert_eq!(
+            val_from_json(&ty("letter"), &json!("ab")),
+            Err(mismatch_at("$", "a single character", "a string"))
+        );
+        assert_eq!(
+            val_from_json(&ty("letter"), &json!("")),
+            Err(mismatch_at("$", "a single character", "a string"))
+        );
+    }
+
+    #[test]
+    fn records_default_missing_options() {
+        assert_eq!(
+            round_trip("person", json!({ "name": "Ada", "age": 36 })),
+            json!({ "name": "Ada", "age": 36, "nick": null })
+        );
+        assert_eq!(
+            val_from_json(&ty("person"), &json!({ "name": "Ada" })),
+            Err(JsonError::MissingField { path: "$".to_string(), field: "age".to_string() })
+        );
+    }
+
+    #[test]
+    fn records_reject_unknown_fields() {
+        assert_eq!(
+            val_from_json(&ty("person"), &json!({ "name": "Ada", "age": 36, "email": "" })),
+            Err(JsonError::UnknownField {
+                path: "$".to_string(),
+                field: "email".to_string(),
+                expected: vec!["name".to_string(), "age".to_string(), "nick".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn errors_locate_nested_values() {
+        let people = json!([{ "name": "Ada", "age": 36 }, { "name": 7, "age": 1 }]);
+        assert_eq!(
+            val_from_json(&ty("people"), &people),
+            Err(mismatch_at("$[1].name", "a string", "a number"))
+        );
+    }
+
+    #[test]
+    fn tuples_check_their_length() {
+        assert_eq!(round_trip("pair", json!([1, "one"])), json!([1, "one"]));
+        assert_eq!(
+            val_from_json(&ty("pair"), &json!([1])),
+            Err(JsonError::LengthMismatch { path: "$".to_string(), expected: 2, found: 1 })
+        );
+    }
+
+    #[test]
+    fn variants_are_names_or_single_case_objects() {
+        assert_eq!(round_trip("shape", json!("none")), json!("none"));
+        assert_eq!(round_trip("shape", json!({ "none": null })), json!("none"));
+        assert_eq!(round_trip("shape", json!({ "circle": 2 })), json!({ "circle": 2 }));
+        assert_eq!(
+            val_from_json(&ty("shape"), &json!("circle")),
+            Err(JsonError::MissingPayload { path: "$".to_string(), case: "circle".to_string() })
+        );
+        assert_eq!(
+            val_from_json(&ty("shape"), &json!({ "none": 1 })),
+            Err(JsonError::UnexpectedPayload { path: "$".to_string(), case: "none".to_string() })
+        );
+        assert_eq!(
+            val_from_json(&ty("shape"), &json!("square")),
+            Err(JsonError::UnknownCase {
+                path: "$".to_string(),
+                case: "square".to_string(),
+                expected: vec!["none".to_string(), "circle".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn enums_and_flags_are_names() {
+        assert_eq!(round_trip("color", json!("green")), json!("green"));
+        assert!(matches!(
+            val_from_json(&ty("color"), &json!("blue")),
+            Err(JsonError::UnknownCase { case, .. }) if case == "blue"
+        ));
+        assert_eq!(round_trip("perms", json!(["read", "write"])), json!(["read", "write"]));
+        assert!(matches!(
+            val_from_json(&ty("perms"), &json!(["read", "exec"])),
+            Err(JsonError::UnknownCase { path, case, .. }) if path == "$[1]" && case == "exec"
+        ));
+    }
+
+    #[test]
+    fn results_are_ok_or_err_objects() {
+        assert_eq!(round_trip("outcome", json!({ "ok": 1 })), json!({ "ok": 1 }));
+        assert_eq!(round_trip("outcome", json!({ "err": "no" })), json!({ "err": "no" }));
+        assert!(matches!(
+            val_from_json(&ty("outcome"), &json!({ "maybe": 1 })),
+            Err(JsonError::UnknownCase { case, .. }) if case == "maybe"
+        ));
+    }
+
+    #[test]
+    fn params_are_read_by_position_or_name() {
+        let func = func();
+        let positional = json!([
+            false, 1, 2, 0.25, "x", "text",
+            { "name": "Ada", "age": 36 }, [], [1, "one"], "none", "red", [], { "ok": 3 }
+        ]);
+        let params = params_from_json(&func, &positional).unwrap();
+        assert_eq!(params.len(), func.params().len());
+
+        let named: Map<String, Value> = func
+            .params()
+            .map(|(name, _)| name.to_string())
+            .zip(positional.as_array().unwrap().iter().cloned())
+            .collect();
+        assert_eq!(params_from_json(&func, &Value::Object(named.clone())), Ok(params));
+
+        let mut missing = named;
+        missing.remove("color");
+        assert_eq!(
+            params_from_json(&func, &Value::Object(missing)),
+            Err(JsonError::MissingField { path: "$".to_string(), field: "color".to_string() })
+        );
+    }
+
+    #[test]
+    fn params_check_their_number_and_shape() {
+        let func = func();
+        assert_eq!(
+            params_from_json(&func, &json!([true])),
+            Err(JsonError::LengthMismatch { path: "$".to_string(), expected: 13, found: 1 })
+        );
+        assert_eq!(
+            params_from_json(&func, &json!("args")),
+            Err(mismatch_at("$", "an array or object of arguments", "a string"))
+        );
+    }
+
+    #[test]
+    fn results_are_unwrapped_when_single() {
+        assert_eq!(results_to_json(&[Val::U32(1)]), Ok(json!(1)));
+        assert_eq!(results_to_json(&[Val::U32(1), Val::Bool(true)]), Ok(json!([1, true])));
+        assert_eq!(results_to_json(&[]), Ok(json!([])));
+    }
+
+    #[test]
+    fn resources_are_not_converted() {
+        let mut store = Store::new(&Engine::default(), ());
+        let resource = Resource::<()>::new_own(0).try_into_resource_any(&mut store).unwrap();
+        assert_eq!(
+            results_to_json(&[Val::U32(1), Val::Resource(resource)]),
+            Err(JsonError::UnsupportedResource { path: "$[1]".to_string() })
+        );
+    }
+}
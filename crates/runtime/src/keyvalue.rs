@@ -0,0 +1,429 @@
+//! A key-value store host interface backed by SQLite.
+//!
+//! The [`KeyValueExtension`] provides the `pawn:keyvalue/store` and `pawn:keyvalue/atomics`
+//! interfaces described in `wit/keyvalue.wit`, so components such as agents can keep data between
+//! calls. Each component gets its own bucket, named after the component, and the size of a bucket
+//! is capped by a quota. Components left with the default name would all share a bucket, so the
+//! extension must be added to named components only.
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use snafu::{ResultExt, Snafu};
+use wasmtime::component::Linker;
+
+use crate::{component::DEFAULT_COMPONENT_NAME, extension::HostExtension, state::State};
+
+mod bindings {
+    wasmtime::component::bindgen!({ world: "keyvalue", path: "wit/keyvalue.wit" });
+}
+
+use bindings::pawn::keyvalue::{
+    atomics,
+    store::{self, KeyResponse},
+};
+
+/// Default size quota of a bucket (16 MiB)
+pub const DEFAULT_BUCKET_QUOTA: usize = 16 * 1024 * 1024;
+
+/// Maximum number of keys returned by a single `list-keys` call.
+const LIST_KEYS_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Snafu)]
+pub enum KeyValueError {
+    #[snafu(display("Failed to open key-value database: {}", source))]
+    DatabaseOpenFailed { source: rusqlite::Error },
+
+    #[snafu(display("Failed to create key-value schema: {}", source))]
+    SchemaCreationFailed { source: rusqlite::Error },
+}
+
+type Result<T, E = KeyValueError> = core::result::Result<T, E>;
+
+/// Provides the `pawn:keyvalue` interfaces to components, storing their data in SQLite.
+///
+/// The database is shared by all the components the extension is added to, and each component
+/// only sees the bucket named after it.
+#[derive(Clone)]
+pub struct KeyValueExtension {
+    /// The connection to the database.
+    db: Arc<Mutex<Connection>>,
+
+    /// The maximum size of a bucket, in bytes of keys and values.
+    quota: usize,
+}
+
+impl KeyValueExtension {
+    /// Opens the key-value store in the SQLite database at `path`, creating it if needed.
+    ///
+    /// # Parameters
+    /// - `path`: The path of the database file.
+    /// - `quota`: The maximum size of a bucket, in bytes of keys and values.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the extension, or an error if the database cannot be opened.
+    pub fn open(path: impl AsRef<Path>, quota: usize) -> Result<Self> {
+        let db = Connection::open(path).context(DatabaseOpenFailedSnafu)?;
+        Self::with_connection(db, quota)
+    }
+
+    /// Creates a key-value store that lives in memory, and is lost when the extension is dropped.
+    ///
+    /// # Parameters
+    /// - `quota`: The maximum size of a bucket, in bytes of keys and values.
+    pub fn in_memory(quota: usize) -> Result<Self> {
+        let db = Connection::open_in_memory().context(DatabaseOpenFailedSnafu)?;
+        Self::with_connection(db, quota)
+    }
+
+    /// Creates the schema of the store in `db` if it does not exist yet.
+    ///
+    /// The size of each bucket is kept in `pawn_keyvalue_usage`, and updated by every write, so
+    /// the quota is checked without summing the whole bucket.
+    fn with_connection(db: Connection, quota: usize) -> Result<Self> {
+        db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS pawn_keyvalue (
+                bucket TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value BLOB NOT NULL,
+                PRIMARY KEY (bucket, key)
+            ) WITHOUT ROWID;
+            CREATE TABLE IF NOT EXISTS pawn_keyvalue_usage (
+                bucket TEXT NOT NULL PRIMARY KEY,
+                size INTEGER NOT NULL
+            ) WITHOUT ROWID;",
+        )
+        .context(SchemaCreationFailedSnafu)?;
+        Ok(Self { db: Arc::new(Mutex::new(db)), quota })
+    }
+}
+
+impl HostExtension for KeyValueExtension {
+    fn name(&self) -> &str {
+        "keyvalue"
+    }
+
+    fn interfaces(&self) -> Vec<String> {
+        vec!["pawn:keyvalue/store@0.1.0".to_string(), "pawn:keyvalue/atomics@0.1.0".to_string()]
+    }
+
+    fn add_to_linker(&self, linker: &mut Linker<State>) -> wasmtime::Result<()> {
+        bindings::Keyvalue::add_to_linker(linker, |state: &mut State| state)
+    }
+
+    fn init_state(&self, state: &mut State) -> wasmtime::Result<()> {
+        if state.name() == DEFAULT_COMPONENT_NAME {
+            return Err(wasmtime::Error::msg(format!(
+                "the key-value store needs a named component, as every component named '{}' \
+                 would share its bucket",
+                DEFAULT_COMPONENT_NAME
+            )));
+        }
+        let bucket =
+            Bucket { name: state.name().to_string(), db: self.db.clone(), quota: self.quota };
+        state.insert_extension(bucket);
+        Ok(())
+    }
+}
+
+/// The bucket of a component, attached to the state of its stores.
+struct Bucket {
+    /// The name of the bucket, which is the name of the component.
+    name: String,
+
+    /// The connection to the database.
+    db: Arc<Mutex<Connection>>,
+
+    /// The maximum size of the bucket, in bytes of keys and values.
+    quota: usize,
+}
+
+impl Bucket {
+    /// Runs `f` in a transaction on the database, committing it if `f` succeeds.
+    fn transaction<T>(
+        &self,
+        f: impl FnOnce(&Transaction<'_>) -> Result<T, store::Error>,
+    ) -> Result<T, store::Error> {
+        let mut db = self.db.lock().unwrap_or_else(PoisonError::into_inner);
+        let tx = db.transaction().map_err(other)?;
+        let result = f(&tx)?;
+        tx.commit().map_err(other)?;
+        Ok(result)
+    }
+
+    /// Writes `value` at `key`, failing if the bucket would exceed its quota.
+    fn write(&self, tx: &Transaction<'_>, key: &str, value: &[u8]) -> Result<(), store::Error> {
+        let size = self
+            .usage(tx)?
+            .saturating_sub(self.entry_size(tx, key)?.unwrap_or(0))
+            .saturating_add(key.len())
+            .saturating_add(value.len());
+        if size > self.quota {
+            return Err(store::Error::QuotaExceeded);
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO pawn_keyvalue (bucket, key, value) VALUES (?1, ?2, ?3)",
+            params![self.name, key, value],
+        )
+        .map_err(other)?;
+        self.set_usage(tx, size)
+    }
+
+    /// Removes `key`, if it is set.
+    fn remove(&self, tx: &Transaction<'_>, key: &str) -> Result<(), store::Error> {
+        let Some(removed) = self.entry_size(tx, key)? else {
+            return Ok(());
+        };
+        tx.execute("DELETE FROM pawn_keyvalue WHERE bucket = ?1 AND key = ?2", params![
+            self.name, key
+        ])
+        .map_err(other)?;
+        let size = self.usage(tx)?.saturating_sub(removed);
+        self.set_usage(tx, size)
+    }
+
+    /// Returns the size of the bucket, in bytes of keys and values.
+    fn usage(&self, db: &Connection) -> Result<usize, store::Error> {
+        let size: Option<i64> = db
+            .query_row(
+                "SELECT size FROM pawn_keyvalue_usage WHERE bucket = ?1",
+                params![self.name],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(other)?;
+        Ok(size.map_or(0, |size| usize::try_from(size).unwrap_or(usize::MAX)))
+    }
+
+    /// Records the size of the bucket, in bytes of keys and values.
+    fn set_usage(&self, tx: &Transaction<'_>, size: usize) -> Result<(), store::Error> {
+        tx.execute(
+            "INSERT OR REPLACE INTO pawn_keyvalue_usage (bucket, size) VALUES (?1, ?2)",
+            params![self.name, i64::try_from(size).unwrap_or(i64::MAX)],
+        )
+        .map_err(other)?;
+        Ok(())
+    }
+
+    /// Returns the size of the entry at `key`, in bytes of key and value, or `None` if it is not
+    /// set.
+    fn entry_size(&self, db: &Connection, key: &str) -> Result<Option<usize>, store::Error> {
+        let size: Option<i64> = db
+            .query_row(
+                "SELECT LENGTH(CAST(key AS BLOB)) + LENGTH(value)
+                FROM pawn_keyvalue WHERE bucket = ?1 AND key = ?2",
+                params![self.name, key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(other)?;
+        Ok(size.map(|size| usize::try_from(size).unwrap_or(usize::MAX)))
+    }
+
+    /// Reads the value at `key`.
+    fn read(&self, db: &Connection, key: &str) -> Result<Option<Vec<u8>>, store::Error> {
+        db.query_row(
+            "SELECT value FROM pawn_keyvalue WHERE bucket = ?1 AND key = ?2",
+            params![self.name, key],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(other)
+    }
+}
+
+// The interfaces are implemented on the state rather than on the bucket, so a call from a store
+// the bucket is not attached to fails instead of panicking
+impl store::Host for State {
+    fn get(&mut self, key: String) -> Result<Option<Vec<u8>>, store::Error> {
+        let bucket = bucket(self)?;
+        let db = bucket.db.lock().unwrap_or_else(PoisonError::into_inner);
+        bucket.read(&db, &key)
+    }
+
+    fn set(&mut self, key: String, value: Vec<u8>) -> Result<(), store::Error> {
+        let bucket = bucket(self)?;
+        bucket.transaction(|tx| bucket.write(tx, &key, &value))
+    }
+
+    fn delete(&mut self, key: String) -> Result<(), store::Error> {
+        let bucket = bucket(self)?;
+        bucket.transaction(|tx| bucket.remove(tx, &key))
+    }
+
+    fn exists(&mut self, key: String) -> Result<bool, store::Error> {
+        let bucket = bucket(self)?;
+        let db = bucket.db.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(bucket.entry_size(&db, &key)?.is_some())
+    }
+
+    fn list_keys(&mut self, cursor: Option<String>) -> Result<KeyResponse, store::Error> {
+        let bucket = bucket(self)?;
+        let db = bucket.db.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut statement = db
+            .prepare_cached(
+                "SELECT key FROM pawn_keyvalue WHERE bucket = ?1 AND (?2 IS NULL OR key > ?2)
+                ORDER BY key LIMIT ?3",
+            )
+            .map_err(other)?;
+        let keys = statement
+            .query_map(params![bucket.name, cursor, LIST_KEYS_PAGE_SIZE as i64], |row| row.get(0))
+            .map_err(other)?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(other)?;
+
+        // A full page may be followed by more keys
+        let cursor = if keys.len() == LIST_KEYS_PAGE_SIZE { keys.last().cloned() } else { None };
+        Ok(KeyResponse { keys, cursor })
+    }
+}
+
+impl atomics::Host for State {
+    fn increment(&mut self, key: String, delta: i64) -> Result<i64, store::Error> {
+        let bucket = bucket(self)?;
+        bucket.transaction(|tx| {
+            let current = match bucket.read(tx, &key)? {
+                Some(value) => {
+                    let bytes = <[u8; 8]>::try_from(value.as_slice()).map_err(|_| {
+                        store::Error::Other(format!("value of '{}' is not an integer", key))
+                    })?;
+                    i64::from_le_bytes(bytes)
+                }
+                None => 0,
+            };
+            let value = current.wrapping_add(delta);
+            bucket.write(tx, &key, &value.to_le_bytes())?;
+            Ok(value)
+        })
+    }
+}
+
+/// Returns the bucket attached to `state` by [`KeyValueExtension::init_state`].
+fn bucket(state: &State) -> Result<&Bucket, store::Error> {
+    state
+        .extension::<Bucket>()
+        .ok_or_else(|| store::Error::Other("no key-value bucket is attached to the store".into()))
+}
+
+/// Converts a database error into the error returned to the component.
+fn other(error: rusqlite::Error) -> store::Error {
+    store::Error::Other(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use wasmtime::component::Val;
+
+    use super::*;
+    use crate::{testing, ComponentBuilder, ComponentError, ComponentRegistry};
+
+    /// A component exporting `hit`, which increments the key `hits` of its bucket and returns the
+    /// new count, or `-1` if the store failed.
+    const COUNTER: &str = r#"
+        (component
+          (import "pawn:keyvalue/atomics@0.1.0" (instance $atomics
+            (type $error (variant (case "quota-exceeded") (case "other" string)))
+            (export "error" (type $error' (eq $error)))
+            (export "increment"
+              (func (param "key" string) (param "delta" s64) (result (result s64 (error $error')))))))
+          (core module $libc
+            (memory (export "memory") 1)
+            (global $next (mut i32) (i32.const 1024))
+            (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+              (local $ptr i32)
+              global.get $next
+              local.get 2
+              i32.add
+              i32.const 1
+              i32.sub
+              i32.const 0
+              local.get 2
+              i32.sub
+              i32.and
+              local.tee $ptr
+              local.get 3
+              i32.add
+              global.set $next
+              local.get $ptr)
+            (data (i32.const 0) "hits"))
+          (core instance $libc (instantiate $libc))
+          (alias core export $libc "memory" (core memory $memory))
+          (alias core export $libc "realloc" (core func $realloc))
+          (core func $increment
+            (canon lower (func $atomics "increment") (memory $memory) (realloc $realloc)))
+          (core module $m
+            (import "libc" "memory" (memory 1))
+            (import "kv" "increment" (func $increment (param i32 i32 i64 i32)))
+            (func (export "hit") (result i64)
+              i32.const 0
+              i32.const 4
+              i64.const 1
+              i32.const 64
+              call $increment
+              i32.const 64
+              i32.load8_u
+              if (result i64)
+                i64.const -1
+              else
+                i32.const 72
+                i64.load
+              end))
+          (core instance $i (instantiate $m
+            (with "libc" (instance $libc))
+            (with "kv" (instance (export "increment" (func $increment))))))
+          (func (export "hit") (result s64) (canon lift (core func $i "hit"))))
+    "#;
+
+    /// Returns a registry holding the `COUNTER` component under each of `names`, with `keyvalue`.
+    fn registry(keyvalue: &KeyValueExtension, names: &[&str]) -> ComponentRegistry {
+        let registry =
+            ComponentRegistry::new(Arc::new(testing::runtime_builder().build().unwrap()));
+        let wasm = testing::wasm(COUNTER);
+        for name in names {
+            let mut builder = ComponentBuilder::default();
+            builder.wasm(&*wasm).extension(keyvalue.clone());
+            registry.register(*name, &builder).unwrap();
+        }
+        registry
+    }
+
+    #[tokio::test]
+    async fn buckets_persist_between_calls_and_are_private() {
+        let keyvalue = KeyValueExtension::in_memory(DEFAULT_BUCKET_QUOTA).unwrap();
+        let registry = registry(&keyvalue, &["agent", "other"]);
+
+        for expected in 1..=3 {
+            let (results, _) = registry.call("agent", None, "hit", &[]).await.unwrap();
+            assert_eq!(results, [Val::S64(expected)]);
+        }
+        let (results, _) = registry.call("other", None, "hit", &[]).await.unwrap();
+        assert_eq!(results, [Val::S64(1)]);
+    }
+
+    #[tokio::test]
+    async fn writes_past_the_quota_fail() {
+        // The key and its 8-byte value take 12 bytes
+        let keyvalue = KeyValueExtension::in_memory(11).unwrap();
+        let registry = registry(&keyvalue, &["agent"]);
+
+        let (results, _) = registry.call("agent", None, "hit", &[]).await.unwrap();
+        assert_eq!(results, [Val::S64(-1)]);
+    }
+
+    #[test]
+    fn unnamed_components_are_refused() {
+        let runtime = testing::runtime_builder().build().unwrap();
+        let keyvalue = KeyValueExtension::in_memory(DEFAULT_BUCKET_QUOTA).unwrap();
+        let wasm = testing::wasm(COUNTER);
+
+        let result =
+            ComponentBuilder::default().wasm(&*wasm).runtime(&runtime).extension(keyvalue).build();
+        assert!(matches!(result, Err(ComponentError::ExtensionInitFailed { .. })));
+    }
+}
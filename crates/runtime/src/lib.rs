@@ -1,9 +1,63 @@
 // #![no_std]
 // extern crate alloc;
 
+mod admission;
+mod cache;
+mod capability;
 mod component;
+mod compose;
+mod config;
+mod coredump;
+mod extension;
+mod http_policy;
+mod http_trigger;
+mod integrity;
+mod introspect;
+mod json;
+#[cfg(feature = "keyvalue")]
+mod keyvalue;
+mod limits;
+mod output;
+mod registry;
+mod report;
 mod runtime;
+mod secrets;
+mod session;
 mod state;
+mod telemetry;
+#[cfg(test)]
+mod testing;
+mod validate;
 
-pub use component::{Component, ComponentError};
-pub use runtime::{Runtime, RuntimeError};
+pub use admission::{AdmissionControl, AdmissionPermit, AdmissionStats, ADMISSION_TIMEOUT};
+pub use cache::{ComponentCache, PRECOMPILED_EXTENSION};
+pub use capability::{CapabilityPolicy, DirAccess, OutputPolicy, Preopen};
+pub use component::{Component, ComponentBuilder, ComponentError, DEFAULT_COMPONENT_NAME};
+pub use config::{
+    AdmissionConfig, CacheConfig, CapabilityConfig, ConfigError, EngineConfig, HttpRuleConfig,
+    IntegrityConfig, PoolingConfig, PreopenConfig, RuntimeConfig, TimeoutConfig,
+    DEFAULT_CAPABILITIES,
+};
+pub use coredump::COREDUMP_EXTENSION;
+pub use extension::HostExtension;
+pub use http_policy::{HttpPolicy, HttpRule};
+pub use http_trigger::{HttpTrigger, HttpTriggerError, INCOMING_HANDLER_INTERFACE};
+pub use integrity::{Integrity, IntegrityError, TrustedKey, SIGNATURE_EXTENSION};
+pub use introspect::{
+    ComponentDescriptor, FunctionDescriptor, InterfaceDescriptor, ItemDescriptor, TypeDescriptor,
+};
+pub use json::{params_from_json, results_to_json, val_from_json, val_to_json, JsonError};
+#[cfg(feature = "keyvalue")]
+pub use keyvalue::{KeyValueError, KeyValueExtension, DEFAULT_BUCKET_QUOTA};
+pub use limits::{LimitExceeded, LimitedResource};
+pub use output::{MAX_CAPTURED_OUTPUT, MAX_ERROR_OUTPUT};
+pub use registry::{ComponentRegistry, RegisteredComponent};
+pub use report::CallReport;
+pub use runtime::{Runtime, RuntimeBuilder, RuntimeError};
+pub use secrets::{Credential, SecretStore};
+pub use session::{Session, SessionEviction};
+pub use state::State;
+#[cfg(feature = "memory-exporter")]
+pub use telemetry::MemorySpanExporter;
+pub use telemetry::TRACER_NAME;
+pub use validate::ValidationProblem;
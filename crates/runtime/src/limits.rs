@@ -0,0 +1,303 @@
+//! Resource accounting and limits for the stores created by the runtime.
+//!
+//! This module provides a `StoreLimiter` that is installed on every store as its
+//! [`ResourceLimiter`], so the runtime can observe how much linear memory a component uses and
+//! enforce its memory, table and instance caps whichever instance allocator the engine uses.
+//!
+//! Growing memories and tables past their caps fails with a [`LimitExceeded`] error. The caps on
+//! the number of instances, memories and tables are checked by Wasmtime itself when a component
+//! is instantiated, and only reported with a message.
+
+use core::fmt;
+
+use snafu::Snafu;
+use wasmtime::ResourceLimiter;
+
+/// A resource whose use is capped in every store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitedResource {
+    /// The total size of the linear memories of a store, in bytes.
+    Memory,
+
+    /// The number of elements of a table.
+    TableElements,
+}
+
+impl fmt::Display for LimitedResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LimitedResource::Memory => "linear memory",
+            LimitedResource::TableElements => "table elements",
+        })
+    }
+}
+
+/// Raised inside a store when a component asks for more of a resource than its limit allows.
+#[derive(Debug, Clone, PartialEq, Eq, Snafu)]
+#[snafu(display("{} limit of {} exceeded, {} requested", resource, limit, requested))]
+pub struct LimitExceeded {
+    /// The resource that ran out.
+    pub resource: LimitedResource,
+
+    /// The limit of the resource.
+    pub limit: usize,
+
+    /// The amount of the resource the component asked for.
+    pub requested: usize,
+}
+
+/// Tracks the resources a store allocates while a component runs, and caps them.
+#[derive(Debug, Clone)]
+pub struct StoreLimiter {
+    /// The total size of all linear memories in the store, in bytes.
+    memory: usize,
+
+    /// The largest value `memory` has reached, in bytes.
+    peak_memory: usize,
+
+    /// The maximum total size of all linear memories in the store, in bytes.
+    max_memory: usize,
+
+    /// The maximum number of elements of a single table.
+    max_table_elements: usize,
+
+    /// The maximum number of core instances in the store.
+    max_instances: usize,
+
+    /// The maximum number of tables in the store.
+    max_tables: usize,
+
+    /// The maximum number of linear memories in the store.
+    max_memories: usize,
+}
+
+impl StoreLimiter {
+    /// Creates a limiter enforcing the given caps.
+    ///
+    /// # Parameters
+    /// - `max_memory`: The maximum total size of all linear memories in the store, in bytes.
+    /// - `max_table_elements`: The maximum number of elements of a single table.
+    /// - `max_instances`: The maximum number of core instances in the store.
+    /// - `max_tables`: The maximum number of tables in the store.
+    /// - `max_memories`: The maximum number of linear memories in the store.
+    pub fn new(
+        max_memory: usize,
+        max_table_elements: usize,
+        max_instances: usize,
+        max_tables: usize,
+        max_memories: usize,
+    ) -> Self {
+        Self {
+            memory: 0,
+            peak_memory: 0,
+            max_memory,
+            max_table_elements,
+            max_instances,
+            max_tables,
+            max_memories,
+        }
+    }
+
+    /// Returns the total size of all linear memories in the store, in bytes.
+    pub fn memory(&self) -> usize {
+        self.memory
+    }
+
+    /// Returns the highest total linear memory size observed in the store, in bytes.
+    pub fn peak_memory(&self) -> usize {
+        self.peak_memory
+    }
+}
+
+impl Default for StoreLimiter {
+    /// Creates a limiter that only tracks resources, without capping them.
+    fn default() -> Self {
+        Self::new(usize::MAX, usize::MAX, usize::MAX, usize::MAX, usize::MAX)
+    }
+}
+
+impl ResourceLimiter for StoreLimiter {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        // The cap holds for all the memories of the store together
+        let memory = self.memory.saturating_add(desired.saturating_sub(current));
+        if memory > self.max_memory {
+            return Err(LimitExceeded {
+                resource: LimitedResource::Memory,
+                limit: self.max_memory,
+                requested: memory,
+            }
+            .into());
+        }
+        self.memory = memory;
+        self.peak_memory = self.peak_memory.max(self.memory);
+        Ok(true)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        if desired > self.max_table_elements {
+            return Err(LimitExceeded {
+                resource: LimitedResource::TableElements,
+                limit: self.max_table_elements,
+                requested: desired,
+            }
+            .into());
+        }
+        Ok(true)
+    }
+
+    fn instances(&self) -> usize {
+        self.max_instances
+    }
+
+    fn tables(&self) -> usize {
+        self.max_tables
+    }
+
+    fn memories(&self) -> usize {
+        self.max_memories
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmtime::{
+        component::{Component, Linker, Val},
+        Engine, Store,
+    };
+
+    use super::*;
+    use crate::{testing, ComponentBuilder, ComponentError};
+
+    /// The size of a WebAssembly page, in bytes.
+    const PAGE: usize = 64 * 1024;
+
+    /// A component of two core instances with a memory of one page each, exporting
+    /// `grow-both`, which grows both memories by a number of pages.
+    const TWO_MEMORIES: &str = r#"
+        (component
+          (core module $m
+            (memory 1)
+            (func (export "grow") (param i32) (result i32)
+              local.get 0
+              memory.grow))
+          (core module $n
+            (import "m" "grow" (func $grow (param i32) (result i32)))
+            (memory 1)
+            (func (export "grow-both") (param i32) (result i32)
+              local.get 0
+              call $grow
+              drop
+              local.get 0
+              memory.grow))
+          (core instance $a (instantiate $m))
+          (core instance $b (instantiate $n (with "m" (instance $a))))
+          (func (export "grow-both") (param "pages" u32) (result s32)
+            (canon lift (core func $b "grow-both"))))
+    "#;
+
+    /// A component exporting `grow-table`, which grows a table of one element.
+    const TABLE: &str = r#"
+        (component
+          (core module $m
+            (table 1 funcref)
+            (func (export "grow-table") (param i32) (result i32)
+              ref.null func
+              local.get 0
+              table.grow))
+          (core instance $i (instantiate $m))
+          (func (export "grow-table") (param "elements" u32) (result s32)
+            (canon lift (core func $i "grow-table"))))
+    "#;
+
+    #[test]
+    fn memory_cap_holds_for_the_whole_store() {
+        let mut limiter = StoreLimiter::new(3 * PAGE, usize::MAX, 10, 10, 10);
+        assert!(limiter.memory_growing(0, 2 * PAGE, None).unwrap());
+        assert!(limiter.memory_growing(0, PAGE, None).unwrap());
+        let error = limiter.memory_growing(PAGE, 2 * PAGE, None).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<LimitExceeded>(),
+            Some(&LimitExceeded {
+                resource: LimitedResource::Memory,
+                limit: 3 * PAGE,
+                requested: 4 * PAGE,
+            })
+        );
+        assert_eq!(limiter.memory(), 3 * PAGE);
+        assert_eq!(limiter.peak_memory(), 3 * PAGE);
+    }
+
+    #[test]
+    fn table_cap_holds_for_each_table() {
+        let mut limiter = StoreLimiter::new(usize::MAX, 4, 10, 10, 10);
+        assert!(limiter.table_growing(0, 4, None).unwrap());
+        assert!(limiter.table_growing(0, 4, None).unwrap());
+        let error = limiter.table_growing(4, 5, None).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<LimitExceeded>(),
+            Some(&LimitExceeded {
+                resource: LimitedResource::TableElements,
+                limit: 4,
+                requested: 5,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn growing_memories_past_the_store_cap_fails() {
+        let runtime = testing::runtime_builder().max_linear_memory(3 * PAGE).build().unwrap();
+        let wasm = testing::wasm(TWO_MEMORIES);
+        let component = ComponentBuilder::default().wasm(&*wasm).runtime(&runtime).build().unwrap();
+
+        // Each memory stays under the cap, but both together do not
+        let result = component.call(None, "grow-both", &[Val::U32(1)]).await;
+        let Err(ComponentError::ResourceLimitExceeded { name, source }) = result else {
+            panic!("expected a resource limit error, got {:?}", result);
+        };
+        assert_eq!(name, "grow-both");
+        assert_eq!(source.resource, LimitedResource::Memory);
+        assert_eq!(source.requested, 4 * PAGE);
+    }
+
+    #[tokio::test]
+    async fn growing_a_table_past_its_cap_fails() {
+        let runtime = testing::runtime_builder().table_elements(4usize).build().unwrap();
+        let wasm = testing::wasm(TABLE);
+
+        let component = ComponentBuilder::default().wasm(&*wasm).runtime(&runtime).build().unwrap();
+        let (results, _) = component.call(None, "grow-table", &[Val::U32(3)]).await.unwrap();
+        assert_eq!(results, [Val::S32(1)]);
+
+        let component = ComponentBuilder::default().wasm(&*wasm).runtime(&runtime).build().unwrap();
+        let result = component.call(None, "grow-table", &[Val::U32(4)]).await;
+        let Err(ComponentError::ResourceLimitExceeded { source, .. }) = result else {
+            panic!("expected a resource limit error, got {:?}", result);
+        };
+        assert_eq!(source, LimitExceeded {
+            resource: LimitedResource::TableElements,
+            limit: 4,
+            requested: 5,
+        });
+    }
+
+    #[test]
+    fn instance_cap_holds_with_the_on_demand_allocator() {
+        let engine = Engine::default();
+        let component = Component::new(&engine, testing::wasm(TWO_MEMORIES)).unwrap();
+        let mut store = Store::new(&engine, StoreLimiter::new(usize::MAX, usize::MAX, 1, 10, 10));
+        store.limiter(|limiter| limiter);
+
+        let error = Linker::new(&engine).instantiate(&mut store, &component).err().unwrap();
+        assert!(error.to_string().contains("instance count"), "{}", error);
+    }
+}
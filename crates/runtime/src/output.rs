@@ -0,0 +1,232 @@
+//! Capture of the standard output and error of components.
+//!
+//! Every line a component writes to a captured stream is emitted as a `tracing` event tagged
+//! with the component name and the id of the call that wrote it. The last bytes of each stream
+//! are also kept in memory, so they can be returned in the call report and attached to errors.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use bytes::Bytes;
+use tracing::{info, warn};
+use wasmtime_wasi::{async_trait, OutputStream, Pollable, StdoutStream, StreamResult};
+
+/// Maximum number of bytes kept from the end of a captured stream.
+pub const MAX_CAPTURED_OUTPUT: usize = 64 * 1024;
+
+/// Maximum number of bytes of standard error attached to a failed call.
+pub const MAX_ERROR_OUTPUT: usize = 4 * 1024;
+
+/// Maximum length of a line, in bytes. Longer lines are emitted in several events.
+const MAX_LINE_LENGTH: usize = 4 * 1024;
+
+/// The source of call ids, shared by all runtimes of the process.
+static NEXT_CALL_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Returns a new id, unique within the process, identifying a call into a component.
+pub(crate) fn next_call_id() -> u64 {
+    NEXT_CALL_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Identifies the component and the call that output is written by.
+#[derive(Debug)]
+pub(crate) struct CallContext {
+    /// The name of the component.
+    component: String,
+
+    /// The id of the current call, or 0 before the first call.
+    call_id: AtomicU64,
+}
+
+impl CallContext {
+    /// Creates the context of a component that has not been called yet.
+    pub(crate) fn new(component: impl Into<String>) -> Self {
+        Self { component: component.into(), call_id: AtomicU64::new(0) }
+    }
+
+    /// Returns the id of the current call.
+    pub(crate) fn call_id(&self) -> u64 {
+        self.call_id.load(Ordering::Relaxed)
+    }
+
+    /// Sets the id of the current call.
+    pub(crate) fn set_call_id(&self, call_id: u64) {
+        self.call_id.store(call_id, Ordering::Relaxed);
+    }
+}
+
+/// The standard stream a capture is attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StreamKind {
+    Stdout,
+    Stderr,
+}
+
+impl StreamKind {
+    /// Returns the name of the stream, as it appears in log events.
+    fn name(self) -> &'static str {
+        match self {
+            StreamKind::Stdout => "stdout",
+            StreamKind::Stderr => "stderr",
+        }
+    }
+}
+
+/// Captures a standard stream of a component into `tracing` and a bounded in-memory tail.
+///
+/// Clones share the same buffer, so the handle kept by the host sees everything written through
+/// the streams handed to the component.
+#[derive(Debug, Clone)]
+pub(crate) struct OutputCapture {
+    /// The stream being captured.
+    kind: StreamKind,
+
+    /// The component and call writing to the stream.
+    context: Arc<CallContext>,
+
+    /// The captured bytes.
+    buffer: Arc<Mutex<CaptureBuffer>>,
+}
+
+/// The bytes captured from a stream.
+#[derive(Debug, Default)]
+struct CaptureBuffer {
+    /// The last `MAX_CAPTURED_OUTPUT` bytes written to the stream.
+    tail: VecDeque<u8>,
+
+    /// The bytes of the current line, not yet emitted.
+    line: Vec<u8>,
+}
+
+impl OutputCapture {
+    /// Creates a capture of `kind` for the component and call described by `context`.
+    pub(crate) fn new(kind: StreamKind, context: Arc<CallContext>) -> Self {
+        Self { kind, context, buffer: Arc::default() }
+    }
+
+    /// Returns the last bytes written to the stream, up to `limit` bytes.
+    pub(crate) fn tail(&self, limit: usize) -> Vec<u8> {
+        let buffer = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        let skip = buffer.tail.len().saturating_sub(limit);
+        buffer.tail.iter().skip(skip).copied().collect()
+    }
+
+    /// Forgets the bytes captured so far, typically at the start of a call.
+    pub(crate) fn clear(&self) {
+        self.buffer.lock().unwrap_or_else(PoisonError::into_inner).tail.clear();
+    }
+
+    /// Emits the current line even if it is not terminated yet, typically at the end of a call.
+    pub(crate) fn flush_line(&self) {
+        let mut buffer = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        if !buffer.line.is_empty() {
+            let line = std::mem::take(&mut buffer.line);
+            self.emit(&line);
+        }
+    }
+
+    /// Emits a line as a `tracing` event.
+    fn emit(&self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches('\r');
+        let component = self.context.component.as_str();
+        let call_id = self.context.call_id();
+        let stream = self.kind.name();
+        match self.kind {
+            StreamKind::Stdout => info!(component, call_id, stream, "{}", line),
+            StreamKind::Stderr => warn!(component, call_id, stream, "{}", line),
+        }
+    }
+}
+
+impl StdoutStream for OutputCapture {
+    fn stream(&self) -> Box<dyn OutputStream> {
+        Box::new(self.clone())
+    }
+
+    fn isatty(&self) -> bool {
+        false
+    }
+}
+
+#[async_trait]
+impl OutputStream for OutputCapture {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        let mut buffer = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // Keep the end of the stream only
+        let excess = (buffer.tail.len() + bytes.len()).saturating_sub(MAX_CAPTURED_OUTPUT);
+        let dropped = excess.min(buffer.tail.len());
+        buffer.tail.drain(..dropped);
+        let start = bytes.len().saturating_sub(MAX_CAPTURED_OUTPUT);
+        buffer.tail.extend(&bytes[start..]);
+
+        for &byte in bytes.iter() {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut buffer.line);
+                self.emit(&line);
+            } else {
+                buffer.line.push(byte);
+                if buffer.line.len() >= MAX_LINE_LENGTH {
+                    let line = std::mem::take(&mut buffer.line);
+                    self.emit(&line);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(MAX_CAPTURED_OUTPUT)
+    }
+}
+
+#[async_trait]
+impl Pollable for OutputCapture {
+    async fn ready(&mut self) {}
+}
+
+/// The captures of the standard streams of a component.
+#[derive(Debug, Clone)]
+pub(crate) struct CapturedOutput {
+    /// The component and call writing to the streams.
+    pub(crate) context: Arc<CallContext>,
+
+    /// The capture of standard output, if the policy captures it.
+    pub(crate) stdout: Option<OutputCapture>,
+
+    /// The capture of standard error, if the policy captures it.
+    pub(crate) stderr: Option<OutputCapture>,
+}
+
+impl CapturedOutput {
+    /// Creates the captures of a component that has not been called yet. No stream is captured
+    /// until one is attached.
+    pub(crate) fn new(component: impl Into<String>) -> Self {
+        Self { context: Arc::new(CallContext::new(component)), stdout: None, stderr: None }
+    }
+
+    /// Creates a capture of `kind` sharing the call context of the other captures.
+    pub(crate) fn capture(&self, kind: StreamKind) -> OutputCapture {
+        OutputCapture::new(kind, self.context.clone())
+    }
+
+    /// Emits the unterminated lines of both streams.
+    pub(crate) fn flush_lines(&self) {
+        self.stdout.iter().chain(&self.stderr).for_each(OutputCapture::flush_line);
+    }
+
+    /// Forgets the bytes captured so far on both streams.
+    pub(crate) fn clear(&self) {
+        self.stdout.iter().chain(&self.stderr).for_each(OutputCapture::clear);
+    }
+}
@@ -0,0 +1,306 @@
+//! A registry of compiled components that can be instantiated many times.
+//!
+//! Compiling a component is by far the most expensive step of running it. The `ComponentRegistry`
+//! compiles and links each component once, and creates a fresh store for every call, so a single
+//! `Runtime` can serve many concurrent requests. A `Session` keeps one store alive across calls
+//! instead, for components that hold state between them.
+
+use core::time::Duration;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use hashbrown::HashMap;
+use wasmtime::component::{ComponentNamedList, Lift, Lower, Val};
+
+use crate::{
+    component::{ComponentBuilder, ComponentPre},
+    introspect::ComponentDescriptor,
+    report::CallReport,
+    session::Session,
+    Component, ComponentError, Runtime,
+};
+
+type Result<T, E = ComponentError> = core::result::Result<T, E>;
+
+/// A component that was compiled and linked by a `ComponentRegistry`.
+pub struct RegisteredComponent {
+    /// The raw WASM binary the component was compiled from.
+    wasm: Vec<u8>,
+
+    /// The compiled component, linked against the host interfaces.
+    pre: ComponentPre,
+}
+
+impl RegisteredComponent {
+    /// Returns the name the component was registered under.
+    pub fn name(&self) -> &str {
+        self.pre.name()
+    }
+
+    /// Describes the imports and exports of the component.
+    pub fn describe(&self) -> ComponentDescriptor {
+        self.pre.describe()
+    }
+
+    /// Returns the compiled component, linked against the host interfaces.
+    pub(crate) fn pre(&self) -> &ComponentPre {
+        &self.pre
+    }
+
+    /// Creates a `Component` with a fresh store, without compiling the component again.
+    ///
+    /// The `Component` is admitted by the runtime's `AdmissionControl` when it is first called.
+    ///
+    /// # Parameters
+    /// - `runtime`: The runtime of the registry the component was registered in.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Component`, ready to be called.
+    pub(crate) fn instantiate<'a>(&'a self, runtime: &'a Runtime) -> Result<Component<'a>> {
+        self.pre.instantiate(&self.wasm, runtime)
+    }
+}
+
+/// A thread-safe registry of compiled components, keyed by name.
+///
+/// The registry can be shared between async tasks (e.g. behind an `Arc`), and every call gets its
+/// own store, so calls to the same component run concurrently without sharing guest state. Calls
+/// are admitted by the runtime's `AdmissionControl`, and wait in its queue when the runtime or the
+/// component is at capacity.
+pub struct ComponentRegistry {
+    /// The runtime used to compile and run the registered components.
+    runtime: Arc<Runtime>,
+
+    /// The registered components, keyed by name.
+    components: RwLock<HashMap<String, Arc<RegisteredComponent>>>,
+}
+
+impl ComponentRegistry {
+    /// Creates an empty registry backed by the given runtime.
+    pub fn new(runtime: Arc<Runtime>) -> Self {
+        Self { runtime, components: RwLock::new(HashMap::new()) }
+    }
+
+    /// Returns the runtime used by the registry.
+    pub fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+
+    /// Compiles a component and registers it under the given name, replacing any component
+    /// previously registered under that name.
+    ///
+    /// The runtime and name set on the builder are ignored in favour of the registry's own.
+    ///
+    /// # Parameters
+    /// - `name`: The name to register the component under.
+    /// - `builder`: A builder describing the component, with at least its WASM binary set.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the registered component.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::ReadWasmFailed`: If the builder has no WASM binary.
+    /// - `ComponentError::WasmComponentCreationFailed`: If the component fails to compile.
+    /// - `ComponentError::ComponentLinkingFailed`: If the component imports are not satisfied.
+    pub fn register(
+        &self,
+        name: impl Into<String>,
+        builder: &ComponentBuilder<'_>,
+    ) -> Result<Arc<RegisteredComponent>> {
+        let name = name.into();
+        let mut builder = builder.clone();
+        builder.name(name.clone());
+        let (pre, wasm) = builder.prepare(&self.runtime)?;
+
+        let registered = Arc::new(RegisteredComponent { wasm: wasm.to_vec(), pre });
+        self.components
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(name, registered.clone());
+        Ok(registered)
+    }
+
+    /// Removes a component from the registry. Calls that are already running are not affected.
+    ///
+    /// # Returns
+    ///
+    /// The removed component, or `None` if no component was registered under the name.
+    pub fn unregister(&self, name: &str) -> Option<Arc<RegisteredComponent>> {
+        self.components.write().unwrap_or_else(PoisonError::into_inner).remove(name)
+    }
+
+    /// Looks up a registered component by name.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::ComponentNotRegistered`: If no component is registered under the name.
+    pub fn get(&self, name: &str) -> Result<Arc<RegisteredComponent>> {
+        self.components
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ComponentError::ComponentNotRegistered { name: name.to_string() })
+    }
+
+    /// Returns the names of all registered components.
+    pub fn names(&self) -> Vec<String> {
+        self.components.read().unwrap_or_else(PoisonError::into_inner).keys().cloned().collect()
+    }
+
+    /// Calls a function of a registered component in a fresh store.
+    ///
+    /// # Parameters
+    /// - `name`: The name the component was registered under.
+    /// - `namespace`: Optional namespace to filter exported functions.
+    /// - `function_name`: The name of the function to call within the WebAssembly component.
+    /// - `params`: The parameters to pass to the function when calling it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the function results and a `CallReport` for the call.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::ComponentNotRegistered`: If no component is registered under the name.
+    /// - `ComponentError::Overloaded`: If the call could not be admitted within the admission
+    ///   timeout of the runtime.
+    /// - Any error returned by [`Component::call`].
+    pub async fn call(
+        &self,
+        name: &str,
+        namespace: Option<&str>,
+        function_name: &str,
+        params: &[Val],
+    ) -> Result<(Vec<Val>, CallReport)> {
+        let registered = self.get(name)?;
+        registered.instantiate(&self.runtime)?.call(namespace, function_name, params).await
+    }
+
+    /// Calls a function of a registered component in a fresh store, with statically typed
+    /// parameters and results.
+    ///
+    /// # Parameters
+    /// - `name`: The name the component was registered under.
+    /// - `namespace`: Optional namespace to filter exported functions.
+    /// - `function_name`: The name of the function to call within the WebAssembly component.
+    /// - `params`: The parameters to pass to the function, as a tuple.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the function results and a `CallReport` for the call.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::ComponentNotRegistered`: If no component is registered under the name.
+    /// - `ComponentError::Overloaded`: If the call could not be admitted within the admission
+    ///   timeout of the runtime.
+    /// - Any error returned by [`Component::call_typed`].
+    pub async fn call_typed<Params, Results>(
+        &self,
+        name: &str,
+        namespace: Option<&str>,
+        function_name: &str,
+        params: Params,
+    ) -> Result<(Results, CallReport)>
+    where
+        Params: ComponentNamedList + Lower + Send + Sync,
+        Results: ComponentNamedList + Lift + Send + Sync,
+    {
+        let registered = self.get(name)?;
+        registered.instantiate(&self.runtime)?.call_typed(namespace, function_name, params).await
+    }
+
+    /// Opens a session of a registered component, which keeps one store and instance alive
+    /// across calls.
+    ///
+    /// The session is admitted like a call, and holds its admission permit until it is dropped
+    /// or evicted. Registering a new version of the component does not affect open sessions.
+    ///
+    /// Idle sessions are evicted by a task spawned on the current Tokio runtime, so this must be
+    /// called from within a Tokio runtime.
+    ///
+    /// # Parameters
+    /// - `name`: The name the component was registered under.
+    /// - `idle_ttl`: How long the session may stay idle before it is evicted.
+    /// - `max_memory`: The memory of the store beyond which the session is evicted, in bytes.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Session`, whose first call instantiates the component.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::SessionTtlInvalid`: If `idle_ttl` is zero.
+    /// - `ComponentError::ComponentNotRegistered`: If no component is registered under the name.
+    /// - `ComponentError::Overloaded`: If the session could not be admitted within the admission
+    ///   timeout of the runtime.
+    /// - Any error returned by [`ComponentPre::new_store`].
+    ///
+    /// # Panics
+    ///
+    /// If it is called outside of a Tokio runtime.
+    pub async fn open_session(
+        &self,
+        name: &str,
+        idle_ttl: Duration,
+        max_memory: usize,
+    ) -> Result<Session> {
+        if idle_ttl.is_zero() {
+            return Err(ComponentError::SessionTtlInvalid { name: name.to_string() });
+        }
+        let registered = self.get(name)?;
+        let permit = self.runtime.admission.acquire(name).await?;
+        Session::new(registered, self.runtime.clone(), permit, idle_ttl, max_memory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn every_call_gets_a_fresh_store() {
+        let registry =
+            ComponentRegistry::new(Arc::new(testing::runtime_builder().build().unwrap()));
+        let wasm = testing::wasm(testing::STATEFUL);
+        registry.register("stateful", ComponentBuilder::default().wasm(&*wasm)).unwrap();
+
+        // The counter starts over, as nothing the guest keeps outlives a call
+        for _ in 0..2 {
+            let (results, _) = registry.call("stateful", None, "next", &[]).await.unwrap();
+            assert_eq!(results, [Val::U32(1)]);
+        }
+        let ((count,), _) =
+            registry.call_typed::<(), (u32,)>("stateful", None, "next", ()).await.unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn components_are_registered_by_name() {
+        let registry =
+            ComponentRegistry::new(Arc::new(testing::runtime_builder().build().unwrap()));
+        let stateful = testing::wasm(testing::STATEFUL);
+        let add = testing::wasm(testing::ADD);
+        let registered =
+            registry.register("math", ComponentBuilder::default().wasm(&*stateful)).unwrap();
+        assert_eq!(registered.name(), "math");
+
+        // Registering under the same name replaces the component
+        registry.register("math", ComponentBuilder::default().wasm(&*add)).unwrap();
+        assert_eq!(registry.names(), ["math"]);
+        let (results, _) =
+            registry.call("math", None, "add", &[Val::U32(1), Val::U32(2)]).await.unwrap();
+        assert_eq!(results, [Val::U32(3)]);
+
+        assert!(registry.unregister("math").is_some());
+        assert!(registry.names().is_empty());
+        let result = registry.call("math", None, "add", &[Val::U32(1), Val::U32(2)]).await;
+        assert!(
+            matches!(result, Err(ComponentError::ComponentNotRegistered { ref name }) if name == "math")
+        );
+    }
+}
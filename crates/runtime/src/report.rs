@@ -0,0 +1,35 @@
+//! Resource usage reports for component calls.
+
+use core::time::Duration;
+
+/// Describes what a single call into a component cost.
+///
+/// A report is returned next to the results of every successful [`Component::call`], and can be
+/// used to bill tenants or to spot components that burn CPU long before they hit the timeout.
+///
+/// [`Component::call`]: crate::Component::call
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallReport {
+    /// The id of the call, which tags the log events of the component's output.
+    pub call_id: u64,
+
+    /// The fuel consumed by the call, or `None` when the runtime does not meter fuel.
+    pub fuel_consumed: Option<u64>,
+
+    /// The peak size of the component's linear memory during the call, in bytes.
+    pub peak_memory: usize,
+
+    /// The wall-clock time spent instantiating the component and running the call.
+    pub wall_time: Duration,
+
+    /// The number of outgoing HTTP requests the component made during the call.
+    pub outgoing_requests: u64,
+
+    /// The end of what the component wrote to its standard output during the call, when its
+    /// capability policy captures it.
+    pub stdout: Option<Vec<u8>>,
+
+    /// The end of what the component wrote to its standard error during the call, when its
+    /// capability policy captures it.
+    pub stderr: Option<Vec<u8>>,
+}
@@ -1,9 +1,27 @@
 use core::time::Duration;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+};
 
 use derive_builder::Builder;
 use snafu::ResultExt;
 use tracing::warn;
-use wasmtime::{Config, Engine, InstanceAllocationStrategy, PoolingAllocationConfig};
+use wasmtime::{Config, Engine, InstanceAllocationStrategy, PoolingAllocationConfig, Store};
+
+use crate::{
+    admission::{AdmissionControl, ADMISSION_TIMEOUT},
+    cache::ComponentCache,
+    config::{ConfigError, RuntimeConfig},
+    integrity::TrustedKey,
+    limits::StoreLimiter,
+    secrets::SecretStore,
+    state::State,
+};
 
 /// Default maximum linear memory for a component (256 MiB)
 pub const MAX_LINEAR_MEMORY: u64 = 256 * 1024 * 1024;
@@ -11,6 +29,8 @@ pub const MAX_LINEAR_MEMORY: u64 = 256 * 1024 * 1024;
 pub const MAX_COMPONENT_SIZE: u64 = 50 * 1024 * 1024;
 /// Default maximum number of components
 pub const MAX_COMPONENTS: u32 = 10_000;
+/// Default interval between two epoch ticks (10 ms)
+pub const EPOCH_TICK_INTERVAL: Duration = Duration::from_millis(10);
 
 #[derive(Debug, snafu::Snafu)]
 pub enum RuntimeError {
@@ -18,6 +38,12 @@ pub enum RuntimeError {
     PoolingAllocatorCreationFailed { source: wasmtime::Error },
     #[snafu(display("Failed to create dynamic allocator: {}", source))]
     DynamicAllocatorCreationFailed { source: wasmtime::Error },
+    #[snafu(display("Failed to start epoch ticker: {}", source))]
+    EpochTickerStartFailed { source: std::io::Error },
+    #[snafu(display("Invalid runtime config: {}", source))]
+    InvalidConfig { source: ConfigError },
+    #[snafu(display("Invalid runtime setting '{}': {}", name, reason))]
+    InvalidSetting { name: &'static str, reason: &'static str },
 }
 
 type Result<T, E = RuntimeError> = core::result::Result<T, E>;
@@ -43,18 +69,83 @@ pub struct Runtime {
     #[builder(default = "Duration::from_secs(10)")]
     pub max_execution_time: Duration,
 
+    /// The interval at which the engine epoch is advanced. Execution deadlines are measured in
+    /// ticks of this interval, so it bounds how late a timeout can fire.
+    #[builder(default = "EPOCH_TICK_INTERVAL")]
+    pub epoch_tick_interval: Duration,
+
+    /// Whether to meter guest execution with fuel. Metering adds overhead to every call, but
+    /// makes the fuel consumed by a call available in its `CallReport`.
+    #[builder(default = "false")]
+    pub consume_fuel: bool,
+
+    /// The fuel budget of each call when `consume_fuel` is enabled, unless the component sets its
+    /// own with `ComponentBuilder::fuel_limit`. A guest that exhausts its budget traps.
+    #[builder(default = "u64::MAX")]
+    pub fuel_limit: u64,
+
+    /// The directory of the persistent compiled-component cache. When set, components are
+    /// compiled once per engine configuration and loaded from disk afterwards.
+    #[builder(default, setter(into, strip_option))]
+    pub cache_dir: Option<PathBuf>,
+
+    /// The directory coredumps are written to when guest code traps, as
+    /// `<component-hash>-<call-id>.coredump`. Coredumps are only captured when this is set.
+    #[builder(default, setter(into, strip_option))]
+    pub coredump_dir: Option<PathBuf>,
+
+    /// The credentials attached to the outgoing HTTP requests of components. Components never
+    /// see them.
+    #[builder(default)]
+    pub secrets: SecretStore,
+
+    /// The ed25519 keys whose signatures are accepted on components.
+    #[builder(default)]
+    pub trusted_keys: Vec<TrustedKey>,
+
+    /// Whether components must carry a pinned digest or a signature. When set, unverified
+    /// components are refused with `ComponentError::IntegrityCheckFailed`.
+    #[builder(default = "false")]
+    pub require_integrity: bool,
+
+    /// The maximum time a call waits for a free instance slot before it fails with
+    /// `ComponentError::Overloaded`.
+    #[builder(default = "ADMISSION_TIMEOUT")]
+    pub admission_timeout: Duration,
+
+    /// Caps the instances in flight and queues the calls past the caps.
+    #[builder(setter(skip))]
+    pub admission: Arc<AdmissionControl>,
+
+    /// The background thread driving epoch interruption for `engine`. It is only held so that
+    /// it stops when the runtime is dropped.
+    #[allow(dead_code)]
+    #[builder(setter(skip))]
+    epoch_ticker: Option<EpochTicker>,
+
     /// The maximum number of components the runtime will manage.
     #[allow(dead_code)]
     #[builder(default = "MAX_COMPONENTS")]
     max_components: u32,
 
-    /// The maximum size for a component, in bytes.
+    /// The maximum number of instances in flight across all components. Defaults to
+    /// `max_components`, the number of instances the pooling allocator has room for.
     #[allow(dead_code)]
+    #[builder(default = "MAX_COMPONENTS as usize")]
+    max_concurrent_instances: usize,
+
+    /// The maximum number of instances in flight for a single component. Defaults to
+    /// `max_concurrent_instances`.
+    #[allow(dead_code)]
+    #[builder(default = "MAX_COMPONENTS as usize")]
+    max_instances_per_component: usize,
+
+    /// The maximum size for a component, in bytes. Larger binaries fail validation.
     #[builder(default = "MAX_COMPONENT_SIZE")]
     max_component_size: usize,
 
-    /// The maximum size for the linear memory, in bytes.
-    #[allow(dead_code)]
+    /// The maximum size for the linear memory, in bytes. It also caps the total size of the
+    /// memories of each store.
     #[builder(default = "MAX_LINEAR_MEMORY")]
     max_linear_memory: usize,
 
@@ -64,7 +155,6 @@ pub struct Runtime {
     force_pooling_allocator: bool,
 
     /// The number of memories each component can have.
-    #[allow(dead_code)]
     #[builder(default = "1")]
     memories_per_component: u32,
 
@@ -74,17 +164,14 @@ pub struct Runtime {
     tables_per_component: u32,
 
     /// The maximum number of core instances that each component can have.
-    #[allow(dead_code)]
     #[builder(default = "30")]
     max_core_instances_per_component: u32,
 
     /// The number of elements in each table.
-    #[allow(dead_code)]
     #[builder(default = "15_000")]
     table_elements: usize,
 
     /// The maximum number of tables a component can have.
-    #[allow(dead_code)]
     #[builder(default = "20")]
     max_tables_per_component: u32,
 }
@@ -98,7 +185,21 @@ impl RuntimeBuilder {
     /// # Returns
     ///
     /// A result containing either the created `Runtime` instance or an error if creation fails.
-    fn build(&self) -> Result<Runtime> {
+    ///
+    /// # Errors
+    ///
+    /// - `RuntimeError::InvalidSetting`: If `epoch_tick_interval` is zero, which would make the
+    ///   epoch ticker spin, or if an instance cap is zero, which would make every call wait out
+    ///   the admission timeout.
+    pub fn build(&self) -> Result<Runtime> {
+        let epoch_tick_interval = self.epoch_tick_interval.unwrap_or(EPOCH_TICK_INTERVAL);
+        if epoch_tick_interval.is_zero() {
+            return Err(RuntimeError::InvalidSetting {
+                name: "epoch_tick_interval",
+                reason: "must be greater than zero",
+            });
+        }
+
         // Extract builder fields with defaults
         let max_components = self.max_components.unwrap_or(MAX_COMPONENTS);
         let max_component_size = self.max_component_size.unwrap_or(MAX_COMPONENT_SIZE as usize);
@@ -109,6 +210,26 @@ impl RuntimeBuilder {
         let max_core_instances_per_component = self.max_core_instances_per_component.unwrap_or(30);
         let table_elements = self.table_elements.unwrap_or(15_000);
         let max_tables_per_component = self.max_tables_per_component.unwrap_or(20);
+        let consume_fuel = self.consume_fuel.unwrap_or(false);
+        let fuel_limit = self.fuel_limit.unwrap_or(u64::MAX);
+        let cache_dir = self.cache_dir.clone().flatten();
+        let coredump_dir = self.coredump_dir.clone().flatten();
+        let max_concurrent_instances =
+            self.max_concurrent_instances.unwrap_or(max_components as usize);
+        let max_instances_per_component =
+            self.max_instances_per_component.unwrap_or(max_concurrent_instances);
+        let admission_timeout = self.admission_timeout.unwrap_or(ADMISSION_TIMEOUT);
+        for (name, cap) in [
+            ("max_concurrent_instances", max_concurrent_instances),
+            ("max_instances_per_component", max_instances_per_component),
+        ] {
+            if cap == 0 {
+                return Err(RuntimeError::InvalidSetting {
+                    name,
+                    reason: "must be greater than zero",
+                });
+            }
+        }
 
         // Configure pooling allocation
         let mut pooling_config = PoolingAllocationConfig::default();
@@ -130,6 +251,9 @@ impl RuntimeBuilder {
         engine_config
             .async_support(true)
             .wasm_component_model(true)
+            .epoch_interruption(true)
+            .consume_fuel(consume_fuel)
+            .coredump_on_trap(coredump_dir.is_some())
             .allocation_strategy(InstanceAllocationStrategy::Pooling(pooling_config));
 
         // Create the engine
@@ -149,11 +273,31 @@ impl RuntimeBuilder {
             }
         };
 
+        let epoch_ticker = EpochTicker::spawn(engine.clone(), epoch_tick_interval)
+            .context(EpochTickerStartFailedSnafu)?;
+
         Ok(Runtime {
             engine,
             engine_config,
             max_execution_time: self.max_execution_time.unwrap_or(Duration::from_secs(10)),
+            epoch_tick_interval,
+            consume_fuel,
+            fuel_limit,
+            cache_dir,
+            coredump_dir,
+            secrets: self.secrets.clone().unwrap_or_default(),
+            trusted_keys: self.trusted_keys.clone().unwrap_or_default(),
+            require_integrity: self.require_integrity.unwrap_or(false),
+            admission_timeout,
+            admission: Arc::new(AdmissionControl::new(
+                max_concurrent_instances,
+                max_instances_per_component,
+                admission_timeout,
+            )),
+            epoch_ticker: Some(epoch_ticker),
             max_components,
+            max_concurrent_instances,
+            max_instances_per_component,
             max_component_size,
             max_linear_memory,
             force_pooling_allocator,
@@ -177,4 +321,254 @@ impl Runtime {
     pub fn new() -> Result<Self> {
         RuntimeBuilder::default().build()
     }
+
+    /// Creates a new `Runtime` instance from a declarative config.
+    ///
+    /// The config is validated first, so a hand-built config is held to the same rules as one
+    /// loaded with `RuntimeConfig::from_file`. Its capability policies are not applied to the
+    /// runtime, and are meant to be passed to components with
+    /// `RuntimeConfig::capability_policy`.
+    ///
+    /// # Parameters
+    /// - `config`: The settings of the runtime.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the created `Runtime` or an error if the config is invalid or the
+    /// creation fails.
+    pub fn from_config(config: &RuntimeConfig) -> Result<Self> {
+        config.validate().context(InvalidConfigSnafu)?;
+
+        let mut builder = RuntimeBuilder::default();
+        builder
+            .max_execution_time(config.max_execution_time())
+            .epoch_tick_interval(config.epoch_tick_interval())
+            .consume_fuel(config.engine.consume_fuel)
+            .fuel_limit(config.engine.fuel_limit)
+            .force_pooling_allocator(config.engine.force_pooling_allocator)
+            .admission_timeout(config.admission_timeout())
+            .max_components(config.pooling.max_components)
+            .max_concurrent_instances(config.max_concurrent_instances())
+            .max_instances_per_component(config.max_instances_per_component())
+            .max_component_size(config.pooling.max_component_size)
+            .max_linear_memory(config.pooling.max_linear_memory)
+            .memories_per_component(config.pooling.memories_per_component)
+            .tables_per_component(config.pooling.tables_per_component)
+            .max_core_instances_per_component(config.pooling.max_core_instances_per_component)
+            .table_elements(config.pooling.table_elements)
+            .max_tables_per_component(config.pooling.max_tables_per_component)
+            .trusted_keys(config.trusted_keys().context(InvalidConfigSnafu)?)
+            .require_integrity(config.integrity.require);
+        if let Some(dir) = &config.cache.dir {
+            builder.cache_dir(dir.clone());
+        }
+        if let Some(dir) = &config.cache.coredump_dir {
+            builder.coredump_dir(dir.clone());
+        }
+        builder.build()
+    }
+
+    /// Returns the number of epoch ticks a store may run before it is interrupted.
+    ///
+    /// The value is `max_execution_time` rounded up to whole `epoch_tick_interval`s, and is
+    /// always at least one tick.
+    pub fn epoch_deadline_ticks(&self) -> u64 {
+        let tick = self.epoch_tick_interval.as_nanos().max(1);
+        let ticks = self.max_execution_time.as_nanos().div_ceil(tick);
+        u64::try_from(ticks).unwrap_or(u64::MAX).max(1)
+    }
+
+    /// Returns the persistent compiled-component cache, if `cache_dir` is configured.
+    pub fn cache(&self) -> Option<ComponentCache> {
+        self.cache_dir.as_ref().map(ComponentCache::new)
+    }
+
+    /// Returns the settings of the stores the runtime creates, which can be kept to create stores
+    /// later without borrowing the runtime.
+    pub(crate) fn store_settings(&self) -> StoreSettings {
+        StoreSettings {
+            engine: self.engine.clone(),
+            secrets: self.secrets.clone(),
+            limiter: self.store_limiter(),
+            epoch_deadline_ticks: self.epoch_deadline_ticks(),
+            fuel_limit: self.consume_fuel.then_some(self.fuel_limit),
+        }
+    }
+}
+
+impl Runtime {
+    /// Returns the maximum size of a component binary, in bytes.
+    pub(crate) fn max_component_size(&self) -> usize {
+        self.max_component_size
+    }
+
+    /// Returns the maximum size of a linear memory, in bytes.
+    pub(crate) fn max_linear_memory(&self) -> usize {
+        self.max_linear_memory
+    }
+
+    /// Creates the resource limiter of a new store from the runtime's pooling limits.
+    fn store_limiter(&self) -> StoreLimiter {
+        let max_instances = self.max_core_instances_per_component as usize;
+        StoreLimiter::new(
+            self.max_linear_memory,
+            self.table_elements,
+            max_instances,
+            self.max_tables_per_component as usize,
+            max_instances.saturating_mul(self.memories_per_component as usize),
+        )
+    }
+}
+
+/// The engine and execution limits of the stores a runtime creates.
+///
+/// Cloning is cheap, since the engine and the credentials are shared between clones.
+#[derive(Clone)]
+pub(crate) struct StoreSettings {
+    /// The engine of the runtime.
+    engine: Engine,
+
+    /// The credentials attached to the outgoing HTTP requests of components.
+    secrets: SecretStore,
+
+    /// The resource limiter each store starts with.
+    limiter: StoreLimiter,
+
+    /// The epoch deadline of each call, in ticks.
+    epoch_deadline_ticks: u64,
+
+    /// The fuel budget of each call, or `None` if the runtime does not consume fuel.
+    fuel_limit: Option<u64>,
+}
+
+impl StoreSettings {
+    /// Returns the credentials attached to the outgoing HTTP requests of components.
+    pub(crate) fn secrets(&self) -> &SecretStore {
+        &self.secrets
+    }
+
+    /// Returns the epoch deadline of each call, in ticks.
+    pub(crate) fn epoch_deadline_ticks(&self) -> u64 {
+        self.epoch_deadline_ticks
+    }
+
+    /// Returns the fuel budget of each call, or `None` if the runtime does not consume fuel.
+    pub(crate) fn fuel_limit(&self) -> Option<u64> {
+        self.fuel_limit
+    }
+
+    /// Creates a new store for the given state, configured with the runtime's execution limits.
+    ///
+    /// The store traps once its epoch deadline is reached. Callers should reset the deadline with
+    /// [`Runtime::epoch_deadline_ticks`] right before running guest code. When fuel metering is
+    /// enabled, the store starts with `fuel_limit` units of fuel.
+    ///
+    /// The memory, table and instance limits of the pooling allocator are also enforced by the
+    /// store itself, so they hold when the runtime falls back to the on-demand allocator.
+    pub(crate) fn new_store(&self, mut state: State) -> Store<State> {
+        *state.limiter() = self.limiter.clone();
+        let mut store = Store::new(&self.engine, state);
+        store.limiter(|state| state.limiter());
+        store.epoch_deadline_trap();
+        store.set_epoch_deadline(self.epoch_deadline_ticks);
+        if let Some(fuel) = self.fuel_limit {
+            // Only fails when fuel is disabled in the engine config
+            let _ = store.set_fuel(fuel);
+        }
+        store
+    }
+}
+
+/// A background thread that periodically increments the epoch of an [`Engine`].
+///
+/// Stores created by the runtime carry an epoch deadline, and guest code traps once the engine
+/// epoch passes it. The thread is stopped and joined when the ticker is dropped.
+struct EpochTicker {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EpochTicker {
+    /// Spawns a thread that increments the epoch of `engine` every `interval`.
+    fn spawn(engine: Engine, interval: Duration) -> std::io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = std::thread::Builder::new().name("pawn-epoch-ticker".to_string()).spawn({
+            let stop = stop.clone();
+            move || {
+                while !stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(interval);
+                    engine.increment_epoch();
+                }
+            }
+        })?;
+        Ok(Self { stop, handle: Some(handle) })
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, ComponentBuilder, ComponentError};
+
+    #[test]
+    fn zero_epoch_tick_interval_is_rejected() {
+        let result = testing::runtime_builder().epoch_tick_interval(Duration::ZERO).build();
+        assert!(matches!(
+            result,
+            Err(RuntimeError::InvalidSetting { name: "epoch_tick_interval", .. })
+        ));
+    }
+
+    #[test]
+    fn zero_instance_caps_are_rejected() {
+        let result = testing::runtime_builder().max_concurrent_instances(0usize).build();
+        assert!(matches!(
+            result,
+            Err(RuntimeError::InvalidSetting { name: "max_concurrent_instances", .. })
+        ));
+        let result = testing::runtime_builder().max_instances_per_component(0usize).build();
+        assert!(matches!(
+            result,
+            Err(RuntimeError::InvalidSetting { name: "max_instances_per_component", .. })
+        ));
+    }
+
+    #[test]
+    fn deadline_rounds_up_to_whole_ticks() {
+        let runtime = testing::runtime_builder()
+            .max_execution_time(Duration::from_millis(25))
+            .epoch_tick_interval(Duration::from_millis(10))
+            .build()
+            .unwrap();
+        assert_eq!(runtime.epoch_deadline_ticks(), 3);
+    }
+
+    #[tokio::test]
+    async fn looping_guest_times_out() {
+        let runtime = testing::runtime_builder()
+            .max_execution_time(Duration::from_millis(50))
+            .epoch_tick_interval(Duration::from_millis(5))
+            .build()
+            .unwrap();
+        let wasm = testing::wasm(testing::SPIN);
+        let component = ComponentBuilder::default().wasm(&*wasm).runtime(&runtime).build().unwrap();
+
+        let started = std::time::Instant::now();
+        let result = component.call(None, "spin", &[]).await;
+        assert!(
+            matches!(&result, Err(ComponentError::ExecutionTimeout { name, .. }) if name == "spin"),
+            "{:?}",
+            result.err()
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
 }
@@ -0,0 +1,171 @@
+//! Credentials attached to outgoing requests by the host.
+//!
+//! Components never see the secrets held by a [`SecretStore`]. When a component sends a request
+//! to a host with a stored credential, the runtime adds the credential to the request on its way
+//! out, so a compromised component has no key to exfiltrate.
+
+use core::fmt;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use hashbrown::HashMap;
+use http::{header::AUTHORIZATION, uri::Authority, HeaderName, HeaderValue, Uri};
+
+use crate::http_policy::authority_matches;
+
+/// A credential attached to the outgoing requests sent to a host.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credential {
+    /// Sent as `Authorization: Bearer <token>`.
+    Bearer(String),
+
+    /// Sent as a custom header, such as `x-goog-api-key`.
+    Header { name: HeaderName, value: String },
+}
+
+impl Credential {
+    /// Returns the name and value of the header carrying the credential, or `None` if the
+    /// credential is not a valid header value.
+    fn header(&self) -> Option<(HeaderName, HeaderValue)> {
+        let (name, value) = match self {
+            Credential::Bearer(token) => (AUTHORIZATION, format!("Bearer {}", token)),
+            Credential::Header { name, value } => (name.clone(), value.clone()),
+        };
+        let mut value = HeaderValue::try_from(value).ok()?;
+        value.set_sensitive(true);
+        Some((name, value))
+    }
+}
+
+impl fmt::Debug for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credential::Bearer(_) => f.write_str("Bearer(<redacted>)"),
+            Credential::Header { name, .. } => {
+                f.debug_struct("Header").field("name", name).field("value", &"<redacted>").finish()
+            }
+        }
+    }
+}
+
+/// The credentials of a runtime, keyed by the host they are sent to.
+///
+/// Clones share the same credentials, so credentials inserted or removed after components were
+/// registered apply to their next requests.
+#[derive(Debug, Clone, Default)]
+pub struct SecretStore {
+    /// The credentials, keyed by a host with an optional port, such as `api.openai.com`.
+    credentials: Arc<RwLock<HashMap<String, Credential>>>,
+}
+
+impl SecretStore {
+    /// Creates an empty secret store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores the credential attached to requests sent to `authority`, replacing any previous one.
+    ///
+    /// # Parameters
+    /// - `authority`: The host the credential is sent to, with an optional port. Without a port,
+    ///   the credential is sent to the host on any port.
+    /// - `credential`: The credential to attach.
+    pub fn insert(&self, authority: impl Into<String>, credential: Credential) {
+        self.credentials
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(authority.into(), credential);
+    }
+
+    /// Removes the credential sent to `authority`, returning whether one was stored.
+    pub fn remove(&self, authority: &str) -> bool {
+        self.credentials.write().unwrap_or_else(PoisonError::into_inner).remove(authority).is_some()
+    }
+
+    /// Returns the header carrying the credential for a request to `uri`, if one is stored.
+    ///
+    /// Credentials are only sent over TLS. When several authorities match the request, a host with
+    /// a port wins over the bare host, and remaining ties go to the first authority in order, so
+    /// the credential sent does not depend on the order of the map.
+    ///
+    /// # Parameters
+    /// - `uri`: The URI of the request.
+    /// - `use_tls`: Whether the request is sent over TLS.
+    pub(crate) fn header_for(&self, uri: &Uri, use_tls: bool) -> Option<(HeaderName, HeaderValue)> {
+        if !use_tls {
+            return None;
+        }
+        let credentials = self.credentials.read().unwrap_or_else(PoisonError::into_inner);
+        credentials
+            .iter()
+            .filter(|(authority, _)| authority_matches(authority, uri))
+            .min_by_key(|(authority, _)| {
+                let port = authority.parse::<Authority>().ok().and_then(|a| a.port_u16());
+                (port.is_none(), authority.as_str())
+            })
+            .and_then(|(_, credential)| credential.header())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the value of the header the store attaches to a request to `uri`.
+    fn header(secrets: &SecretStore, uri: &str, use_tls: bool) -> Option<(HeaderName, String)> {
+        let (name, value) = secrets.header_for(&uri.parse().unwrap(), use_tls)?;
+        Some((name, value.to_str().unwrap().to_string()))
+    }
+
+    #[test]
+    fn credentials_are_sent_to_their_host() {
+        let secrets = SecretStore::new();
+        secrets.insert("api.example.com", Credential::Bearer("token".to_string()));
+        let key = HeaderName::from_static("x-api-key");
+        secrets.insert("keys.example.com", Credential::Header {
+            name: key.clone(),
+            value: "k".into(),
+        });
+
+        assert_eq!(
+            header(&secrets, "https://api.example.com/v1", true),
+            Some((AUTHORIZATION, "Bearer token".to_string()))
+        );
+        assert_eq!(header(&secrets, "https://keys.example.com/", true), Some((key, "k".into())));
+        assert_eq!(header(&secrets, "https://example.com/", true), None);
+
+        assert!(secrets.remove("api.example.com"));
+        assert_eq!(header(&secrets, "https://api.example.com/v1", true), None);
+    }
+
+    #[test]
+    fn credentials_are_not_sent_without_tls() {
+        let secrets = SecretStore::new();
+        secrets.insert("api.example.com", Credential::Bearer("token".to_string()));
+        assert_eq!(header(&secrets, "http://api.example.com/v1", false), None);
+    }
+
+    #[test]
+    fn overlapping_authorities_prefer_the_port() {
+        let secrets = SecretStore::new();
+        secrets.insert("api.example.com", Credential::Bearer("any".to_string()));
+        secrets.insert("api.example.com:443", Credential::Bearer("https".to_string()));
+        secrets.insert("API.example.com:443", Credential::Bearer("upper".to_string()));
+
+        // Both authorities with a port match, and the first in order wins
+        assert_eq!(
+            header(&secrets, "https://api.example.com/", true),
+            Some((AUTHORIZATION, "Bearer upper".to_string()))
+        );
+        assert_eq!(
+            header(&secrets, "https://api.example.com:8443/", true),
+            Some((AUTHORIZATION, "Bearer any".to_string()))
+        );
+    }
+
+    #[test]
+    fn credentials_are_redacted_from_debug_output() {
+        let credential = Credential::Bearer("token".to_string());
+        assert!(!format!("{:?}", credential).contains("token"));
+        assert!(credential.header().unwrap().1.is_sensitive());
+    }
+}
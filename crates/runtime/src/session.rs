@@ -0,0 +1,490 @@
+//! Stateful sessions that keep a component instance alive across calls.
+//!
+//! A call through the `ComponentRegistry` runs in a fresh store, so nothing the guest keeps in
+//! memory outlives it. A [`Session`] holds a single store and the instance living in it, so a
+//! multi-turn agent can keep warm state, such as a conversation cache, from one call to the next.
+//!
+//! The instance of a session occupies a slot of the runtime for as long as it lives, so the
+//! session holds an admission permit until it is dropped or evicted. It is evicted, dropping its
+//! store and releasing the permit, once it stays idle for longer than its TTL, once the memory of
+//! its store grows past its threshold, or once a call leaves the instance unusable. Idle sessions
+//! are evicted by a background task, so a session the host forgot about does not keep its slot.
+//! Calls to an evicted session fail with `ComponentError::SessionExpired`, and the host opens a
+//! new one.
+
+use core::time::Duration;
+use std::{
+    sync::{Arc, Mutex, MutexGuard, PoisonError, Weak},
+    time::Instant,
+};
+
+use snafu::Snafu;
+use tracing::info;
+use wasmtime::{
+    component::{ComponentNamedList, Instance, Lift, Lower, Val},
+    Store,
+};
+
+use crate::{
+    admission::AdmissionPermit,
+    component::{CallContext, ComponentError},
+    registry::RegisteredComponent,
+    report::CallReport,
+    state::State,
+    Runtime,
+};
+
+type Result<T, E = ComponentError> = core::result::Result<T, E>;
+
+/// Why a session was evicted.
+#[derive(Debug, Clone, PartialEq, Eq, Snafu)]
+pub enum SessionEviction {
+    #[snafu(display("idle for {:?}, longer than its TTL of {:?}", idle, ttl))]
+    Idle { idle: Duration, ttl: Duration },
+
+    #[snafu(display("memory grew to {} bytes, more than the limit of {} bytes", memory, limit))]
+    MemoryExceeded { memory: usize, limit: usize },
+
+    #[snafu(display("a call failed and left the instance unusable"))]
+    CallFailed,
+
+    #[snafu(display("a call was cancelled before it returned"))]
+    CallCancelled,
+}
+
+/// A component instance kept alive across calls, with the store holding its state.
+///
+/// Calls run one at a time, each with the execution limits of the runtime, against the same
+/// instance. The report of a call describes that call, except for `peak_memory`, which covers the
+/// whole session.
+pub struct Session {
+    /// The component the session runs.
+    component: Arc<RegisteredComponent>,
+
+    /// The runtime of the registry the component was registered in.
+    runtime: Arc<Runtime>,
+
+    /// The resources of the session and when it was last used, shared with the task evicting the
+    /// session once it stays idle.
+    state: Arc<Mutex<SessionState>>,
+
+    /// How long the session may stay idle before it is evicted.
+    idle_ttl: Duration,
+
+    /// The memory of the store beyond which the session is evicted, in bytes.
+    max_memory: usize,
+}
+
+/// The state of a session, shared between the session and the task evicting it.
+struct SessionState {
+    /// The resources of the session, or what became of them.
+    slot: Slot,
+
+    /// When the last call returned, or when the session was opened.
+    last_used: Instant,
+}
+
+/// Where the resources of a session are.
+enum Slot {
+    /// The session is live and no call is running.
+    Ready(LiveSession),
+
+    /// A call took the resources of the session. A call that is cancelled drops them, and leaves
+    /// the session in this state until the next call evicts it.
+    InCall,
+
+    /// The session was evicted, and its resources dropped.
+    Evicted(SessionEviction),
+}
+
+/// The resources a session holds until it is evicted.
+struct LiveSession {
+    /// The store in which the component is instantiated.
+    store: Store<State>,
+
+    /// The instance of the component, created by the first call.
+    instance: Option<Instance>,
+
+    /// The slots of the runtime the instance occupies.
+    _permit: AdmissionPermit,
+}
+
+impl Session {
+    /// Opens a session with a fresh store, and spawns the task evicting it once it stays idle on
+    /// the current Tokio runtime.
+    ///
+    /// `idle_ttl` must not be zero, or the task would wake up in a loop.
+    ///
+    /// # Parameters
+    /// - `component`: The component the session runs.
+    /// - `runtime`: The runtime of the registry the component was registered in.
+    /// - `permit`: The admission permit the session holds until it is evicted.
+    /// - `idle_ttl`: How long the session may stay idle before it is evicted.
+    /// - `max_memory`: The memory of the store beyond which the session is evicted, in bytes.
+    ///
+    /// # Errors
+    ///
+    /// - Any error returned by [`crate::component::ComponentPre::new_store`].
+    ///
+    /// # Panics
+    ///
+    /// If it is called outside of a Tokio runtime.
+    pub(crate) fn new(
+        component: Arc<RegisteredComponent>,
+        runtime: Arc<Runtime>,
+        permit: AdmissionPermit,
+        idle_ttl: Duration,
+        max_memory: usize,
+    ) -> Result<Self> {
+        let store = component.pre().new_store(&runtime)?;
+        let live = LiveSession { store, instance: None, _permit: permit };
+        let state = Arc::new(Mutex::new(SessionState {
+            slot: Slot::Ready(live),
+            last_used: Instant::now(),
+        }));
+        tokio::spawn(evict_when_idle(
+            Arc::downgrade(&state),
+            component.name().to_string(),
+            idle_ttl,
+            max_memory,
+        ));
+        Ok(Self { component, runtime, state, idle_ttl, max_memory })
+    }
+
+    /// Returns the name of the component the session runs.
+    pub fn name(&self) -> &str {
+        self.component.name()
+    }
+
+    /// Returns how long the session has been idle.
+    pub fn idle(&self) -> Duration {
+        self.state().last_used.elapsed()
+    }
+
+    /// Returns the total size of the linear memories of the store, in bytes, or `0` once the
+    /// session is evicted or its last call was cancelled.
+    pub fn memory(&self) -> usize {
+        self.state().memory()
+    }
+
+    /// Returns why the session was evicted, or `None` if it is still live.
+    pub fn eviction(&self) -> Option<SessionEviction> {
+        match &self.state().slot {
+            Slot::Evicted(reason) => Some(reason.clone()),
+            Slot::Ready(_) | Slot::InCall => None,
+        }
+    }
+
+    /// Evicts the session if it has been idle for longer than its TTL, or if its memory grew past
+    /// its threshold.
+    ///
+    /// Sessions are checked before and after every call, and idle sessions are evicted by a
+    /// background task, so calling this is only needed to check a session right away.
+    ///
+    /// # Returns
+    ///
+    /// Whether the session is evicted, now or by an earlier check.
+    pub fn evict_if_expired(&mut self) -> bool {
+        let (name, idle_ttl, max_memory) = (self.component.name(), self.idle_ttl, self.max_memory);
+        self.state().evict_if_expired(name, idle_ttl, max_memory)
+    }
+
+    /// Calls a function of the component, against the instance of the session.
+    ///
+    /// The component is instantiated by the first call, so the state it keeps in memory is seen
+    /// by every later call.
+    ///
+    /// # Parameters
+    /// - `namespace`: Optional namespace to filter exported functions.
+    /// - `function_name`: The name of the function to call within the WebAssembly component.
+    /// - `params`: The parameters to pass to the function when calling it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the function results and a `CallReport` for the call.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::SessionExpired`: If the session was evicted, before or by this call.
+    ///   A call that fails in guest code, runs past a limit or fails to instantiate the component
+    ///   returns its own error and evicts the session.
+    /// - Any error returned by [`crate::Component::call`].
+    pub async fn call(
+        &mut self,
+        namespace: Option<&str>,
+        function_name: &str,
+        params: &[Val],
+    ) -> Result<(Vec<Val>, CallReport)> {
+        let mut live = self.begin_call()?;
+        let result = self.call_context(&mut live).call(namespace, function_name, params).await;
+        self.end_call(live, result)
+    }
+
+    /// Calls a function of the component with statically typed parameters and results, against
+    /// the instance of the session.
+    ///
+    /// # Parameters
+    /// - `namespace`: Optional path of the instance exporting the function.
+    /// - `function_name`: The name of the function to call within the WebAssembly component.
+    /// - `params`: The parameters to pass to the function, as a tuple.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the function results, as a tuple, and a `CallReport` for the call.
+    ///
+    /// # Errors
+    ///
+    /// - Any error returned by [`Session::call`] or [`crate::Component::call_typed`].
+    pub async fn call_typed<Params, Results>(
+        &mut self,
+        namespace: Option<&str>,
+        function_name: &str,
+        params: Params,
+    ) -> Result<(Results, CallReport)>
+    where
+        Params: ComponentNamedList + Lower + Send + Sync,
+        Results: ComponentNamedList + Lift + Send + Sync,
+    {
+        let mut live = self.begin_call()?;
+        let result =
+            self.call_context(&mut live).call_typed(namespace, function_name, params).await;
+        self.end_call(live, result)
+    }
+
+    /// Locks the state of the session.
+    fn state(&self) -> MutexGuard<'_, SessionState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Checks that the session is still live, and takes its resources for the duration of a call.
+    ///
+    /// # Errors
+    ///
+    /// - `ComponentError::SessionExpired`: If the session is evicted, or the previous call was
+    ///   cancelled.
+    fn begin_call(&mut self) -> Result<LiveSession> {
+        let (name, idle_ttl, max_memory) = (self.component.name(), self.idle_ttl, self.max_memory);
+        let mut state = self.state();
+        state.evict_if_expired(name, idle_ttl, max_memory);
+
+        let reason = match core::mem::replace(&mut state.slot, Slot::InCall) {
+            Slot::Ready(live) => return Ok(live),
+            Slot::InCall => {
+                state.evict(name, SessionEviction::CallCancelled);
+                SessionEviction::CallCancelled
+            }
+            Slot::Evicted(reason) => {
+                state.slot = Slot::Evicted(reason.clone());
+                reason
+            }
+        };
+        Err(ComponentError::SessionExpired { name: name.to_string(), reason })
+    }
+
+    /// Returns the context a call runs in, with the resources of the session.
+    fn call_context<'a>(&'a self, live: &'a mut LiveSession) -> CallContext<'a> {
+        CallContext {
+            name: self.component.name(),
+            pre: self.component.pre(),
+            store: &mut live.store,
+            instance: &mut live.instance,
+            runtime: &self.runtime,
+        }
+    }
+
+    /// Gives the resources back to the session once a call returned, and evicts the session if
+    /// the call left it unusable or grew its memory past the threshold.
+    fn end_call<T>(&mut self, live: LiveSession, result: Result<T>) -> Result<T> {
+        let (name, idle_ttl, max_memory) = (self.component.name(), self.idle_ttl, self.max_memory);
+        let mut state = self.state();
+        state.slot = Slot::Ready(live);
+        state.last_used = Instant::now();
+
+        if result.as_ref().is_err_and(leaves_instance_unusable) {
+            state.evict(name, SessionEviction::CallFailed);
+        }
+        state.evict_if_expired(name, idle_ttl, max_memory);
+        result
+    }
+}
+
+impl SessionState {
+    /// Returns the total size of the linear memories of the store, in bytes, or `0` if the
+    /// session does not hold it.
+    fn memory(&self) -> usize {
+        match &self.slot {
+            Slot::Ready(live) => live.store.data().limits().memory(),
+            Slot::InCall | Slot::Evicted(_) => 0,
+        }
+    }
+
+    /// Evicts the session of component `name` if no call is running and it has been idle for
+    /// longer than `idle_ttl`, or if its memory grew past `max_memory`.
+    ///
+    /// # Returns
+    ///
+    /// Whether the session is evicted, now or by an earlier check.
+    fn evict_if_expired(&mut self, name: &str, idle_ttl: Duration, max_memory: usize) -> bool {
+        if let Slot::Ready(_) = self.slot {
+            let idle = self.last_used.elapsed();
+            let memory = self.memory();
+            if idle > idle_ttl {
+                self.evict(name, SessionEviction::Idle { idle, ttl: idle_ttl });
+            } else if memory > max_memory {
+                self.evict(name, SessionEviction::MemoryExceeded { memory, limit: max_memory });
+            }
+        }
+        matches!(self.slot, Slot::Evicted(_))
+    }
+
+    /// Drops the store and instance of the session of component `name`, if it still holds them,
+    /// and releases its admission permit.
+    fn evict(&mut self, name: &str, reason: SessionEviction) {
+        if !matches!(self.slot, Slot::Evicted(_)) {
+            info!("Evicted session of component {}: {}", name, reason);
+            self.slot = Slot::Evicted(reason);
+        }
+    }
+}
+
+/// Evicts the session sharing `state` once it stays idle for longer than `idle_ttl`, so a session
+/// its holder abandoned still releases its admission permit. Returns once the session is evicted
+/// or dropped.
+async fn evict_when_idle(
+    state: Weak<Mutex<SessionState>>,
+    name: String,
+    idle_ttl: Duration,
+    max_memory: usize,
+) {
+    let mut wait = idle_ttl;
+    loop {
+        tokio::time::sleep(wait).await;
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.evict_if_expired(&name, idle_ttl, max_memory) {
+            return;
+        }
+        // A running call restarts the idle time once it returns
+        wait = match state.slot {
+            Slot::Ready(_) => idle_ttl.saturating_sub(state.last_used.elapsed()),
+            Slot::InCall | Slot::Evicted(_) => idle_ttl,
+        };
+    }
+}
+
+/// Returns whether a call that failed with `error` may have left the instance in a state where it
+/// cannot be called again, such as a trap in the middle of guest code.
+fn leaves_instance_unusable(error: &ComponentError) -> bool {
+    matches!(
+        error,
+        ComponentError::ComponentFunctionCallFailed { .. }
+            | ComponentError::ExecutionTimeout { .. }
+            | ComponentError::FuelExhausted { .. }
+            | ComponentError::ResourceLimitExceeded { .. }
+            | ComponentError::WasmComponentInstantiateFailed { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, ComponentBuilder, ComponentRegistry};
+
+    /// The size of a page of linear memory, in bytes.
+    const PAGE: usize = 64 * 1024;
+
+    /// Returns a registry holding the `STATEFUL` component as `stateful`.
+    fn registry() -> ComponentRegistry {
+        let registry =
+            ComponentRegistry::new(Arc::new(testing::runtime_builder().build().unwrap()));
+        let wasm = testing::wasm(testing::STATEFUL);
+        registry.register("stateful", ComponentBuilder::default().wasm(&*wasm)).unwrap();
+        registry
+    }
+
+    /// Returns whether `result` failed because the session was evicted for a reason matching
+    /// `expected`.
+    fn is_expired(
+        result: Result<(Vec<Val>, CallReport)>,
+        expected: impl Fn(&SessionEviction) -> bool,
+    ) -> bool {
+        matches!(result, Err(ComponentError::SessionExpired { reason, .. }) if expected(&reason))
+    }
+
+    #[tokio::test]
+    async fn calls_share_the_instance() {
+        let registry = registry();
+        let mut session =
+            registry.open_session("stateful", Duration::from_secs(60), 4 * PAGE).await.unwrap();
+
+        for expected in 1..=3 {
+            let (results, _) = session.call(None, "next", &[]).await.unwrap();
+            assert_eq!(results, [Val::U32(expected)]);
+        }
+        assert_eq!(session.eviction(), None);
+    }
+
+    #[tokio::test]
+    async fn idle_sessions_are_evicted_and_release_their_slot() {
+        let registry = registry();
+        let mut session =
+            registry.open_session("stateful", Duration::from_millis(20), 4 * PAGE).await.unwrap();
+        session.call(None, "next", &[]).await.unwrap();
+        assert_eq!(registry.runtime().admission.stats().in_flight, 1);
+
+        // The background task evicts the session without any call
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(matches!(session.eviction(), Some(SessionEviction::Idle { .. })));
+        assert_eq!(registry.runtime().admission.stats().in_flight, 0);
+        let result = session.call(None, "next", &[]).await;
+        assert!(is_expired(result, |reason| matches!(reason, SessionEviction::Idle { .. })));
+    }
+
+    #[tokio::test]
+    async fn sessions_growing_past_their_memory_are_evicted() {
+        let registry = registry();
+        let mut session =
+            registry.open_session("stateful", Duration::from_secs(60), 2 * PAGE).await.unwrap();
+
+        // Growing to the threshold keeps the session, growing past it evicts it after the call
+        let (results, _) = session.call(None, "grow", &[]).await.unwrap();
+        assert_eq!(results, [Val::S32(1)]);
+        assert_eq!(session.memory(), 2 * PAGE);
+        let (results, _) = session.call(None, "grow", &[]).await.unwrap();
+        assert_eq!(results, [Val::S32(2)]);
+        assert_eq!(
+            session.eviction(),
+            Some(SessionEviction::MemoryExceeded { memory: 3 * PAGE, limit: 2 * PAGE })
+        );
+        assert_eq!(session.memory(), 0);
+        let result = session.call(None, "next", &[]).await;
+        assert!(is_expired(result, |reason| matches!(
+            reason,
+            SessionEviction::MemoryExceeded { .. }
+        )));
+    }
+
+    #[tokio::test]
+    async fn failed_calls_evict_the_session() {
+        let registry = registry();
+        let mut session =
+            registry.open_session("stateful", Duration::from_secs(60), 4 * PAGE).await.unwrap();
+
+        let result = session.call(None, "trap", &[]).await;
+        assert!(matches!(result, Err(ComponentError::ComponentFunctionCallFailed { .. })));
+        assert_eq!(session.eviction(), Some(SessionEviction::CallFailed));
+        let result = session.call(None, "next", &[]).await;
+        assert!(is_expired(result, |reason| *reason == SessionEviction::CallFailed));
+    }
+
+    #[tokio::test]
+    async fn zero_idle_ttl_is_rejected() {
+        let registry = registry();
+
+        let result = registry.open_session("stateful", Duration::ZERO, 4 * PAGE).await;
+        assert!(matches!(result, Err(ComponentError::SessionTtlInvalid { .. })));
+        assert_eq!(registry.runtime().admission.stats().admitted, 0);
+    }
+}
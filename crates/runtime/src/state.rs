@@ -1,12 +1,47 @@
+use std::{sync::Arc, time::Instant};
+
+use hashbrown::HashMap;
+use opentelemetry::{
+    trace::{SpanKind, TraceContextExt},
+    Context, KeyValue,
+};
+use tracing::warn;
 use wasmtime::component::ResourceTable;
-use wasmtime_wasi::{IoView, WasiCtx, WasiCtxBuilder, WasiView};
-use wasmtime_wasi_http::{WasiHttpCtx, WasiHttpView};
+use wasmtime_wasi::{IoView, WasiCtx, WasiView};
+use wasmtime_wasi_http::{
+    bindings::http::types::ErrorCode,
+    body::HyperOutgoingBody,
+    types::{
+        default_send_request_handler, HostFutureIncomingResponse, IncomingResponse,
+        OutgoingRequestConfig,
+    },
+    HttpResult, WasiHttpCtx, WasiHttpView,
+};
+
+use crate::{
+    capability::CapabilityPolicy,
+    compose::LinkedComponent,
+    extension::Extensions,
+    limits::StoreLimiter,
+    output::{next_call_id, CapturedOutput, MAX_CAPTURED_OUTPUT, MAX_ERROR_OUTPUT},
+    secrets::SecretStore,
+    telemetry::{self, COMPONENT, HTTP_METHOD, HTTP_STATUS, SERVER_ADDRESS, URL},
+};
 
 /// Represents the state used by the WebAssembly component, including a resource table and a WASI context.
 ///
 /// This struct encapsulates the resources and environment needed for the execution of WebAssembly modules
 /// with WASI support.
 pub struct State {
+    /// The name of the component, used to identify it in logs.
+    name: String,
+
+    /// The capabilities granted to the component.
+    policy: Arc<CapabilityPolicy>,
+
+    /// The credentials attached to the component's outgoing requests.
+    secrets: SecretStore,
+
     http: WasiHttpCtx,
 
     /// The resource table which stores the resources (e.g., memory, tables) for the WebAssembly component.
@@ -14,19 +49,166 @@ pub struct State {
 
     /// The WASI context which provides access to the WASI environment.
     ctx: WasiCtx,
+
+    /// The resource limiter which tracks the memory allocated by the component.
+    limiter: StoreLimiter,
+
+    /// The number of outgoing HTTP requests sent by the component.
+    outgoing_requests: u64,
+
+    /// The output captured from the component's standard streams.
+    captured: CapturedOutput,
+
+    /// The data attached by host extensions.
+    extensions: Extensions,
+
+    /// The components providing linked imports, keyed by import name.
+    linked: HashMap<String, LinkedComponent>,
+
+    /// The trace context of the current call, parent of the spans of its outgoing requests.
+    trace_context: Context,
 }
 
 impl State {
-    /// Creates a new `State` instance with a default resource table and WASI context.
+    /// Creates a new `State` instance with a default resource table and a WASI context granting
+    /// the capabilities of `policy`.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the component, used to identify it in logs.
+    /// - `policy`: The capabilities granted to the component.
+    /// - `secrets`: The credentials attached to the component's outgoing requests.
     ///
     /// # Returns
     ///
-    /// A new `State` instance with the default resources and context.
-    pub fn new() -> Self {
+    /// A `Result` containing the new `State`, or an error if a capability cannot be granted.
+    pub(crate) fn new(
+        name: impl Into<String>,
+        policy: Arc<CapabilityPolicy>,
+        secrets: SecretStore,
+    ) -> wasmtime::Result<Self> {
+        let name = name.into();
         let table = ResourceTable::new();
-        let ctx = WasiCtxBuilder::new().build();
+        let (ctx, captured) = policy.build_ctx(&name)?;
         let http = WasiHttpCtx::new();
-        Self { table, ctx, http }
+        let limiter = StoreLimiter::default();
+        Ok(Self {
+            name,
+            policy,
+            secrets,
+            table,
+            ctx,
+            http,
+            limiter,
+            outgoing_requests: 0,
+            captured,
+            extensions: Extensions::default(),
+            linked: HashMap::new(),
+            trace_context: Context::new(),
+        })
+    }
+
+    /// Returns a mutable reference to the resource limiter of the store.
+    pub(crate) fn limiter(&mut self) -> &mut StoreLimiter {
+        &mut self.limiter
+    }
+
+    /// Returns the name of the component the state belongs to.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Attaches data of a host extension to the state, replacing the data of the same type.
+    ///
+    /// # Returns
+    ///
+    /// The data of the same type that was attached before, if any.
+    pub fn insert_extension<T: Send + 'static>(&mut self, value: T) -> Option<T> {
+        self.extensions.insert(value)
+    }
+
+    /// Returns the extension data of type `T`, if it is attached.
+    pub fn extension<T: Send + 'static>(&self) -> Option<&T> {
+        self.extensions.get()
+    }
+
+    /// Returns the extension data of type `T` mutably, if it is attached.
+    pub fn extension_mut<T: Send + 'static>(&mut self) -> Option<&mut T> {
+        self.extensions.get_mut()
+    }
+
+    /// Detaches and returns the extension data of type `T`, if it is attached.
+    pub fn remove_extension<T: Send + 'static>(&mut self) -> Option<T> {
+        self.extensions.remove()
+    }
+
+    /// Attaches the component providing the import named `import`.
+    pub(crate) fn link(&mut self, import: String, linked: LinkedComponent) {
+        self.linked.insert(import, linked);
+    }
+
+    /// Returns the component providing the import named `import`, if one is linked.
+    pub(crate) fn linked_mut(&mut self, import: &str) -> Option<&mut LinkedComponent> {
+        self.linked.get_mut(import)
+    }
+
+    /// Returns the trace context of the current call.
+    pub(crate) fn trace_context(&self) -> &Context {
+        &self.trace_context
+    }
+
+    /// Sets the trace context of the current call.
+    pub(crate) fn set_trace_context(&mut self, cx: Context) {
+        self.trace_context = cx;
+    }
+
+    /// Returns the resource limiter of the store.
+    pub(crate) fn limits(&self) -> &StoreLimiter {
+        &self.limiter
+    }
+
+    /// Returns the number of outgoing HTTP requests sent by the current call.
+    pub(crate) fn outgoing_requests(&self) -> u64 {
+        self.outgoing_requests
+    }
+
+    /// Starts a new call, tagging the output written from now on with a new call id.
+    ///
+    /// The outgoing requests and captured output of the previous call in the store are reset, so
+    /// they describe the new call only.
+    ///
+    /// # Returns
+    ///
+    /// The id of the call.
+    pub(crate) fn begin_call(&mut self) -> u64 {
+        let call_id = next_call_id();
+        self.captured.context.set_call_id(call_id);
+        self.captured.clear();
+        self.outgoing_requests = 0;
+        call_id
+    }
+
+    /// Ends the current call, emitting the output lines the component left unterminated.
+    pub(crate) fn end_call(&self) {
+        self.captured.flush_lines();
+    }
+
+    /// Returns the end of the captured standard output, if the policy captures it.
+    pub(crate) fn captured_stdout(&self) -> Option<Vec<u8>> {
+        self.captured.stdout.as_ref().map(|capture| capture.tail(MAX_CAPTURED_OUTPUT))
+    }
+
+    /// Returns the end of the captured standard error, if the policy captures it.
+    pub(crate) fn captured_stderr(&self) -> Option<Vec<u8>> {
+        self.captured.stderr.as_ref().map(|capture| capture.tail(MAX_CAPTURED_OUTPUT))
+    }
+
+    /// Returns the last lines of the captured standard error, to explain a failed call, or
+    /// `None` if nothing was captured.
+    pub(crate) fn stderr_tail(&self) -> Option<String> {
+        let tail = self.captured.stderr.as_ref()?.tail(MAX_ERROR_OUTPUT);
+        let tail = String::from_utf8_lossy(&tail);
+        let tail = tail.trim_end();
+        (!tail.is_empty()).then(|| tail.to_string())
     }
 }
 
@@ -54,4 +236,76 @@ impl WasiHttpView for State {
     fn ctx(&mut self) -> &mut WasiHttpCtx {
         &mut self.http
     }
+
+    /// Sends an outgoing request on behalf of the component, counting it for the call report.
+    ///
+    /// Requests that the component's `HttpPolicy` does not allow fail with
+    /// `ErrorCode::HttpRequestDenied`. Allowed requests sent over TLS to a host with a stored
+    /// credential carry that credential, replacing any header of the same name set by the
+    /// component.
+    ///
+    /// Every request is traced in a client span, child of the span of the current call, and
+    /// carries the trace context of that span in its headers.
+    fn send_request(
+        &mut self,
+        mut request: http::Request<HyperOutgoingBody>,
+        config: OutgoingRequestConfig,
+    ) -> HttpResult<HostFutureIncomingResponse> {
+        self.outgoing_requests += 1;
+        let started = Instant::now();
+        let cx = telemetry::start_span(
+            request.method().to_string(),
+            SpanKind::Client,
+            vec![
+                KeyValue::new(COMPONENT, self.name.clone()),
+                KeyValue::new(HTTP_METHOD, request.method().to_string()),
+                KeyValue::new(URL, request.uri().to_string()),
+                KeyValue::new(SERVER_ADDRESS, request.uri().host().unwrap_or_default().to_string()),
+            ],
+            &self.trace_context,
+        );
+
+        if !self.policy.http.allows(&request) {
+            warn!(
+                "Denied outgoing request of component {}: {} {}",
+                self.name,
+                request.method(),
+                request.uri()
+            );
+            let error = ErrorCode::HttpRequestDenied;
+            telemetry::end_span(&cx, started, Some(&error));
+            return Err(error.into());
+        }
+        if let Some((name, value)) = self.secrets.header_for(request.uri(), config.use_tls) {
+            request.headers_mut().insert(name, value);
+        }
+        telemetry::inject_context(&cx, request.headers_mut());
+
+        let handle = wasmtime_wasi::runtime::spawn(async move {
+            let response = default_send_request_handler(request, config).await;
+            end_request_span(&cx, started, &response);
+            Ok(response)
+        });
+        Ok(HostFutureIncomingResponse::pending(handle))
+    }
+}
+
+/// Ends the span of an outgoing request once its response headers arrived, or it failed.
+fn end_request_span(
+    cx: &Context,
+    started: Instant,
+    response: &Result<IncomingResponse, ErrorCode>,
+) {
+    match response {
+        Ok(response) => {
+            let status = response.resp.status();
+            cx.span().set_attribute(KeyValue::new(HTTP_STATUS, i64::from(status.as_u16())));
+            if status.is_client_error() || status.is_server_error() {
+                telemetry::end_span(cx, started, Some(&status));
+            } else {
+                telemetry::end_span(cx, started, None);
+            }
+        }
+        Err(error) => telemetry::end_span(cx, started, Some(error)),
+    }
 }
@@ -0,0 +1,160 @@
+//! OpenTelemetry spans for compilation, instantiation, calls and outgoing requests.
+//!
+//! Spans are created with the global tracer provider, under the [`TRACER_NAME`] tracer, so they
+//! cost next to nothing until an embedder installs a provider. The trace context of a call is
+//! kept in the [`State`] of its store, which makes instantiation and the component's outgoing
+//! requests children of the call span, and is injected into outgoing request headers with the
+//! global text map propagator.
+//!
+//! [`State`]: crate::State
+
+use std::{borrow::Cow, fmt::Display, time::Instant};
+
+use http::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry::{
+    global::{self, BoxedTracer},
+    propagation::Injector,
+    trace::{SpanBuilder, SpanKind, Status, TraceContextExt},
+    Context, KeyValue,
+};
+
+/// The name of the tracer the runtime creates its spans with.
+pub const TRACER_NAME: &str = "pawn-runtime";
+
+/// The attribute holding the name of the component.
+pub(crate) const COMPONENT: &str = "pawn.component";
+
+/// The attribute holding the exported function, as `namespace#function`.
+pub(crate) const FUNCTION: &str = "pawn.function";
+
+/// The attribute holding the id of the call.
+pub(crate) const CALL_ID: &str = "pawn.call_id";
+
+/// The attribute holding the duration of the operation, in milliseconds.
+pub(crate) const LATENCY_MS: &str = "pawn.latency_ms";
+
+/// The attribute holding the method of an outgoing request.
+pub(crate) const HTTP_METHOD: &str = "http.request.method";
+
+/// The attribute holding the URL of an outgoing request.
+pub(crate) const URL: &str = "url.full";
+
+/// The attribute holding the host an outgoing request is sent to.
+pub(crate) const SERVER_ADDRESS: &str = "server.address";
+
+/// The attribute holding the status code of the response to an outgoing request.
+pub(crate) const HTTP_STATUS: &str = "http.response.status_code";
+
+/// Returns the tracer of the runtime, from the global tracer provider.
+pub(crate) fn tracer() -> BoxedTracer {
+    global::tracer(TRACER_NAME)
+}
+
+/// Starts a span named `name` as a child of `parent`, and returns the context holding it.
+pub(crate) fn start_span(
+    name: impl Into<Cow<'static, str>>,
+    kind: SpanKind,
+    attributes: Vec<KeyValue>,
+    parent: &Context,
+) -> Context {
+    let tracer = tracer();
+    let span = SpanBuilder::from_name(name)
+        .with_kind(kind)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, parent);
+    parent.with_span(span)
+}
+
+/// Ends the span of `cx`, recording the time elapsed since `started` and the error, if any.
+pub(crate) fn end_span(cx: &Context, started: Instant, error: Option<&dyn Display>) {
+    let span = cx.span();
+    span.set_attribute(KeyValue::new(LATENCY_MS, started.elapsed().as_secs_f64() * 1000.0));
+    if let Some(error) = error {
+        span.set_status(Status::error(error.to_string()));
+    }
+    span.end();
+}
+
+/// Writes the trace context of `cx` into the headers of an outgoing request.
+pub(crate) fn inject_context(cx: &Context, headers: &mut HeaderMap) {
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(cx, &mut HeaderInjector(headers))
+    });
+}
+
+/// Writes the fields of a text map propagator into HTTP headers.
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) =
+            (HeaderName::from_bytes(key.as_bytes()), HeaderValue::from_str(&value))
+        {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+#[cfg(feature = "memory-exporter")]
+pub use memory::MemorySpanExporter;
+
+#[cfg(feature = "memory-exporter")]
+mod memory {
+    use std::{
+        future::{ready, Future},
+        pin::Pin,
+        sync::{Arc, Mutex, PoisonError},
+    };
+
+    use opentelemetry::global;
+    use opentelemetry_sdk::{
+        export::trace::{ExportResult, SpanData, SpanExporter},
+        propagation::TraceContextPropagator,
+        trace::TracerProvider,
+    };
+
+    /// Keeps the spans it exports in memory, so they can be inspected locally.
+    ///
+    /// Clones share the same spans, so a clone can be handed to a tracer provider while the
+    /// original is used to read them back.
+    #[derive(Debug, Clone, Default)]
+    pub struct MemorySpanExporter {
+        /// The spans exported so far.
+        spans: Arc<Mutex<Vec<SpanData>>>,
+    }
+
+    impl MemorySpanExporter {
+        /// Installs a global tracer provider exporting every span to this exporter as soon as it
+        /// ends, and the W3C trace context propagator.
+        ///
+        /// # Returns
+        ///
+        /// The installed tracer provider, which can be used to flush or shut it down.
+        pub fn install(&self) -> TracerProvider {
+            let provider = TracerProvider::builder().with_simple_exporter(self.clone()).build();
+            global::set_tracer_provider(provider.clone());
+            global::set_text_map_propagator(TraceContextPropagator::new());
+            provider
+        }
+
+        /// Returns the spans exported so far, in the order they ended.
+        pub fn finished_spans(&self) -> Vec<SpanData> {
+            self.spans.lock().unwrap_or_else(PoisonError::into_inner).clone()
+        }
+
+        /// Drops the spans exported so far.
+        pub fn reset(&self) {
+            self.spans.lock().unwrap_or_else(PoisonError::into_inner).clear();
+        }
+    }
+
+    impl SpanExporter for MemorySpanExporter {
+        fn export(
+            &mut self,
+            mut batch: Vec<SpanData>,
+        ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+            self.spans.lock().unwrap_or_else(PoisonError::into_inner).append(&mut batch);
+            Box::pin(ready(Ok(())))
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Runtimes and guest components shared by the unit tests.
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::RuntimeBuilder;
+
+/// A component exporting `spin`, which loops forever.
+pub(crate) const SPIN: &str = r#"
+    (component
+      (core module $m
+        (func (export "spin") (loop $l (br $l))))
+      (core instance $i (instantiate $m))
+      (func (export "spin") (canon lift (core func $i "spin"))))
+"#;
+
+/// A component exporting `add`, which adds two `u32`s.
+pub(crate) const ADD: &str = r#"
+    (component
+      (core module $m
+        (func (export "add") (param i32 i32) (result i32)
+          local.get 0
+          local.get 1
+          i32.add))
+      (core instance $i (instantiate $m))
+      (func (export "add") (param "a" u32) (param "b" u32) (result u32)
+        (canon lift (core func $i "add"))))
+"#;
+
+/// A component counting its calls to `next`, growing its memory by a page with `grow` and
+/// trapping in `trap`.
+pub(crate) const STATEFUL: &str = r#"
+    (component
+      (core module $m
+        (memory 1)
+        (global $count (mut i32) (i32.const 0))
+        (func (export "next") (result i32)
+          global.get $count
+          i32.const 1
+          i32.add
+          global.set $count
+          global.get $count)
+        (func (export "grow") (result i32)
+          i32.const 1
+          memory.grow)
+        (func (export "trap")
+          unreachable))
+      (core instance $i (instantiate $m))
+      (func (export "next") (result u32) (canon lift (core func $i "next")))
+      (func (export "grow") (result s32) (canon lift (core func $i "grow")))
+      (func (export "trap") (canon lift (core func $i "trap"))))
+"#;
+
+/// Returns a builder for a runtime sized for tests, which only has room for a few components.
+pub(crate) fn runtime_builder() -> RuntimeBuilder {
+    let mut builder = RuntimeBuilder::default();
+    builder
+        .max_components(16u32)
+        .max_execution_time(Duration::from_secs(5))
+        .admission_timeout(Duration::from_secs(5));
+    builder
+}
+
+/// Compiles a component from its text format.
+pub(crate) fn wasm(wat: &str) -> Vec<u8> {
+    wat::parse_str(wat).unwrap()
+}
+
+/// A directory under the system's temporary directory, removed with its content when dropped.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates an empty directory whose name starts with `prefix`, unique to the test process.
+    pub(crate) fn new(prefix: &str) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("{}-{}-{}", prefix, std::process::id(), id));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    /// Returns the path of the directory.
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
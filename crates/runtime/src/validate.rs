@@ -0,0 +1,359 @@
+//! Validation of component binaries before they are compiled.
+//!
+//! Compiling a component is expensive, and some problems only surface once it is instantiated,
+//! such as an import that nothing provides. A [`Validator`] checks a binary with `wasmparser`
+//! first, and reports every problem it finds at once as a list of [`ValidationProblem`]s:
+//!
+//! - the binary must not exceed the runtime's `max_component_size`;
+//! - it must be a valid component using only the WebAssembly features the runtime supports;
+//! - the initial size of its memories must fit in the runtime's `max_linear_memory`;
+//! - each of its imports must be provided by WASI, `wasi:http`, a host extension or a linked
+//!   component.
+
+use snafu::Snafu;
+use wasmparser::{Encoding, Parser, Payload, Validator as WasmValidator, WasmFeatures};
+
+/// The WebAssembly features components may use. Proposals such as threads, exceptions, garbage
+/// collection or 64-bit memories are not enabled in the engine.
+const SUPPORTED_FEATURES: WasmFeatures = WasmFeatures::WASM2
+    .union(WasmFeatures::COMPONENT_MODEL)
+    .union(WasmFeatures::MULTI_MEMORY)
+    .union(WasmFeatures::RELAXED_SIMD)
+    .union(WasmFeatures::TAIL_CALL)
+    .union(WasmFeatures::EXTENDED_CONST);
+
+/// The interfaces of WASI and `wasi:http` the linker of every component provides.
+const HOST_INTERFACES: &[&str] = &[
+    "wasi:cli/environment@0.2.0",
+    "wasi:cli/exit@0.2.0",
+    "wasi:cli/stdin@0.2.0",
+    "wasi:cli/stdout@0.2.0",
+    "wasi:cli/stderr@0.2.0",
+    "wasi:cli/terminal-input@0.2.0",
+    "wasi:cli/terminal-output@0.2.0",
+    "wasi:cli/terminal-stdin@0.2.0",
+    "wasi:cli/terminal-stdout@0.2.0",
+    "wasi:cli/terminal-stderr@0.2.0",
+    "wasi:clocks/monotonic-clock@0.2.0",
+    "wasi:clocks/wall-clock@0.2.0",
+    "wasi:filesystem/types@0.2.0",
+    "wasi:filesystem/preopens@0.2.0",
+    "wasi:io/error@0.2.0",
+    "wasi:io/poll@0.2.0",
+    "wasi:io/streams@0.2.0",
+    "wasi:random/random@0.2.0",
+    "wasi:random/insecure@0.2.0",
+    "wasi:random/insecure-seed@0.2.0",
+    "wasi:sockets/network@0.2.0",
+    "wasi:sockets/instance-network@0.2.0",
+    "wasi:sockets/udp@0.2.0",
+    "wasi:sockets/udp-create-socket@0.2.0",
+    "wasi:sockets/tcp@0.2.0",
+    "wasi:sockets/tcp-create-socket@0.2.0",
+    "wasi:sockets/ip-name-lookup@0.2.0",
+    "wasi:http/types@0.2.0",
+    "wasi:http/outgoing-handler@0.2.0",
+];
+
+/// The size of a WebAssembly page, in bytes.
+const PAGE_SIZE: u64 = 64 * 1024;
+
+/// A problem found in a component binary before compiling it.
+#[derive(Debug, Clone, PartialEq, Eq, Snafu)]
+pub enum ValidationProblem {
+    #[snafu(display("binary is {} bytes, more than the limit of {} bytes", size, limit))]
+    TooLarge { size: usize, limit: usize },
+
+    #[snafu(display("binary is a core module, not a component"))]
+    NotAComponent,
+
+    #[snafu(display("invalid binary at offset {:#x}: {}", offset, message))]
+    Invalid { offset: usize, message: String },
+
+    #[snafu(display("unsupported WebAssembly feature at offset {:#x}: {}", offset, message))]
+    UnsupportedFeature { offset: usize, message: String },
+
+    #[snafu(display(
+        "memory starts at {} bytes, more than the limit of {} bytes",
+        initial,
+        limit
+    ))]
+    MemoryTooLarge { initial: u64, limit: usize },
+
+    #[snafu(display("import '{}' is not provided by the host or a linked component", name))]
+    UnknownImport { name: String },
+}
+
+/// Checks component binaries against the limits of a runtime and the interfaces it provides.
+pub(crate) struct Validator {
+    /// The maximum size of a binary, in bytes.
+    max_size: usize,
+
+    /// The maximum initial size of a memory, in bytes.
+    max_linear_memory: usize,
+
+    /// The interfaces the component may import, in addition to `HOST_INTERFACES`. `None` skips
+    /// the import check, for when a host extension does not list its interfaces.
+    interfaces: Option<Vec<String>>,
+}
+
+impl Validator {
+    /// Creates a validator.
+    ///
+    /// # Parameters
+    /// - `max_size`: The maximum size of a binary, in bytes.
+    /// - `max_linear_memory`: The maximum initial size of a memory, in bytes.
+    /// - `interfaces`: The interfaces provided beyond WASI and `wasi:http`, or `None` if they are
+    ///   not all known.
+    pub(crate) fn new(
+        max_size: usize,
+        max_linear_memory: usize,
+        interfaces: Option<Vec<String>>,
+    ) -> Self {
+        Self { max_size, max_linear_memory, interfaces }
+    }
+
+    /// Checks a component binary.
+    ///
+    /// # Returns
+    ///
+    /// The problems found in the binary, empty if it passes validation. A binary that is too large
+    /// or malformed is not inspected further, so it yields a single problem.
+    pub(crate) fn validate(&self, wasm: &[u8]) -> Vec<ValidationProblem> {
+        if wasm.len() > self.max_size {
+            return vec![ValidationProblem::TooLarge { size: wasm.len(), limit: self.max_size }];
+        }
+
+        if let Err(e) = WasmValidator::new_with_features(SUPPORTED_FEATURES).validate_all(wasm) {
+            let (offset, message) = (e.offset(), e.message().to_string());
+            // Tell binaries using a disabled proposal apart from malformed ones
+            let problem =
+                match WasmValidator::new_with_features(WasmFeatures::all()).validate_all(wasm) {
+                    Ok(_) => ValidationProblem::UnsupportedFeature { offset, message },
+                    Err(_) => ValidationProblem::Invalid { offset, message },
+                };
+            // A core module fails validation as soon as it uses a component feature
+            return match is_core_module(wasm) {
+                true => vec![ValidationProblem::NotAComponent],
+                false => vec![problem],
+            };
+        }
+
+        let mut problems = Vec::new();
+        let mut depth = 0_usize;
+        for payload in Parser::new(0).parse_all(wasm) {
+            // The binary passed validation, so it parses
+            let Ok(payload) = payload else { break };
+            match payload {
+                Payload::Version { encoding, .. } => {
+                    depth += 1;
+                    if depth == 1 && encoding == Encoding::Module {
+                        return vec![ValidationProblem::NotAComponent];
+                    }
+                }
+                Payload::End(_) => depth = depth.saturating_sub(1),
+                Payload::MemorySection(memories) => {
+                    for memory in memories.into_iter().flatten() {
+                        let page_size = memory.page_size_log2.map_or(PAGE_SIZE, |log2| 1 << log2);
+                        let initial = memory.initial.saturating_mul(page_size);
+                        if initial > self.max_linear_memory as u64 {
+                            problems.push(ValidationProblem::MemoryTooLarge {
+                                initial,
+                                limit: self.max_linear_memory,
+                            });
+                        }
+                    }
+                }
+                Payload::ComponentImportSection(imports) if depth == 1 => {
+                    let Some(interfaces) = &self.interfaces else { continue };
+                    for import in imports.into_iter().flatten() {
+                        let name = import.name.0;
+                        let provided = HOST_INTERFACES
+                            .iter()
+                            .copied()
+                            .chain(interfaces.iter().map(String::as_str))
+                            .any(|provided| interface_matches(provided, name));
+                        if !provided {
+                            problems
+                                .push(ValidationProblem::UnknownImport { name: name.to_string() });
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        problems
+    }
+}
+
+/// Returns whether `wasm` starts with the header of a core module.
+fn is_core_module(wasm: &[u8]) -> bool {
+    matches!(
+        Parser::new(0).parse_all(wasm).next(),
+        Some(Ok(Payload::Version { encoding: Encoding::Module, .. }))
+    )
+}
+
+/// Returns whether a provided interface satisfies an import.
+///
+/// Like the linker, a provided version satisfies the imports of any semver-compatible version,
+/// and an interface named without a version satisfies the imports of every version.
+fn interface_matches(provided: &str, import: &str) -> bool {
+    let (provided_name, provided_version) = split_version(provided);
+    let (import_name, import_version) = split_version(import);
+    provided_name == import_name
+        && match (provided_version, import_version) {
+            (Some(provided), Some(import)) => compatible_versions(provided, import),
+            _ => true,
+        }
+}
+
+/// Splits an interface name such as `wasi:io/poll@0.2.0` into its name and version.
+fn split_version(name: &str) -> (&str, Option<&str>) {
+    match name.split_once('@') {
+        Some((name, version)) => (name, Some(version)),
+        None => (name, None),
+    }
+}
+
+/// Returns whether two versions are semver-compatible: they share their major version, or their
+/// minor version when the major version is zero.
+fn compatible_versions(a: &str, b: &str) -> bool {
+    significant_version(a) == significant_version(b)
+}
+
+/// Returns the parts of a version that semver compatibility depends on.
+fn significant_version(version: &str) -> (&str, &str) {
+    let mut parts = version.split(['.', '-', '+']);
+    match parts.next().unwrap_or_default() {
+        "0" => ("0", parts.next().unwrap_or_default()),
+        major => (major, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A validator with generous limits that knows the interfaces of a chat extension.
+    fn validator() -> Validator {
+        Validator::new(1 << 20, 1 << 20, Some(vec!["pawn:chat/handler@0.1.0".to_string()]))
+    }
+
+    /// Validates the component written as WAT.
+    fn problems(validator: &Validator, wat: &str) -> Vec<ValidationProblem> {
+        validator.validate(&wat::parse_str(wat).unwrap())
+    }
+
+    #[test]
+    fn valid_component_has_no_problems() {
+        let wat = r#"
+            (component
+              (import "wasi:cli/stdout@0.2.0" (instance))
+              (import "pawn:chat/handler@0.1.0" (instance))
+              (core module (memory 1)))
+        "#;
+        assert_eq!(problems(&validator(), wat), vec![]);
+    }
+
+    #[test]
+    fn oversized_binary_is_not_inspected() {
+        let wasm = wat::parse_str("(component (core module (memory 100)))").unwrap();
+        let validator = Validator::new(8, 1 << 20, None);
+        assert_eq!(validator.validate(&wasm), vec![ValidationProblem::TooLarge {
+            size: wasm.len(),
+            limit: 8
+        }]);
+    }
+
+    #[test]
+    fn core_module_is_not_a_component() {
+        assert_eq!(problems(&validator(), "(module (memory 1))"), vec![
+            ValidationProblem::NotAComponent
+        ]);
+    }
+
+    #[test]
+    fn malformed_binary_is_invalid() {
+        let mut wasm = wat::parse_str("(component)").unwrap();
+        wasm.extend_from_slice(&[0xff, 0x00]);
+        assert!(matches!(validator().validate(&wasm)[..], [ValidationProblem::Invalid { .. }]));
+    }
+
+    #[test]
+    fn disabled_proposals_are_unsupported_features() {
+        for module in ["(memory i64 1)", "(memory 1 1 shared)", "(tag)"] {
+            let wat = format!("(component (core module {}))", module);
+            assert!(
+                matches!(problems(&validator(), &wat)[..], [
+                    ValidationProblem::UnsupportedFeature { .. }
+                ]),
+                "{}",
+                module
+            );
+        }
+    }
+
+    #[test]
+    fn memories_are_checked_against_the_limit() {
+        let validator = Validator::new(1 << 20, 2 * PAGE_SIZE as usize, None);
+        let wat = r#"
+            (component
+              (core module (memory 2))
+              (core module (memory 3))
+              (component (core module (memory 4))))
+        "#;
+        assert_eq!(problems(&validator, wat), vec![
+            ValidationProblem::MemoryTooLarge {
+                initial: 3 * PAGE_SIZE,
+                limit: 2 * PAGE_SIZE as usize
+            },
+            ValidationProblem::MemoryTooLarge {
+                initial: 4 * PAGE_SIZE,
+                limit: 2 * PAGE_SIZE as usize
+            },
+        ]);
+    }
+
+    #[test]
+    fn unknown_imports_are_reported_together() {
+        let wat = r#"
+            (component
+              (import "wasi:cli/stdout@0.2.3" (instance))
+              (import "pawn:chat/handler@0.2.0" (instance))
+              (import "pawn:agent/tools" (instance))
+              (component
+                (import "pawn:nested/only" (instance))))
+        "#;
+        assert_eq!(problems(&validator(), wat), vec![
+            ValidationProblem::UnknownImport { name: "pawn:chat/handler@0.2.0".to_string() },
+            ValidationProblem::UnknownImport { name: "pawn:agent/tools".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn imports_are_not_checked_when_interfaces_are_unknown() {
+        let validator = Validator::new(1 << 20, 1 << 20, None);
+        assert_eq!(
+            problems(&validator, r#"(component (import "pawn:agent/tools" (instance)))"#),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn interfaces_match_semver_compatible_versions() {
+        for (provided, import, matches) in [
+            ("wasi:io/poll@0.2.0", "wasi:io/poll@0.2.0", true),
+            ("wasi:io/poll@0.2.0", "wasi:io/poll@0.2.3", true),
+            ("wasi:io/poll@0.2.0", "wasi:io/poll@0.3.0", false),
+            ("wasi:io/poll@0.2.0", "wasi:io/poll@0.2.0-rc-2023-11-10", true),
+            ("pawn:chat/handler@1.0.0", "pawn:chat/handler@1.4.2", true),
+            ("pawn:chat/handler@1.0.0", "pawn:chat/handler@2.0.0", false),
+            ("pawn:chat/handler", "pawn:chat/handler@2.0.0", true),
+            ("pawn:chat/handler@1.0.0", "pawn:chat/handler", true),
+            ("wasi:io/poll@0.2.0", "wasi:io/streams@0.2.0", false),
+        ] {
+            assert_eq!(interface_matches(provided, import), matches, "{} / {}", provided, import);
+        }
+    }
+}